@@ -1,5 +1,5 @@
 pub mod msg;
-mod helper;
+pub mod helper;
 mod state;
 pub mod contract;
 mod error;
\ No newline at end of file
@@ -1,6 +1,6 @@
 use cosmwasm_schema::write_api;
 
-use swap_extention::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+use swap_sparrow::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
 
 fn main() {
     write_api! {
@@ -1,10 +1,9 @@
-use cosmwasm_std::{entry_point, Binary, Deps, DepsMut, Env, MessageInfo, Response, StdResult, to_binary};
+use cosmwasm_std::{entry_point, Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdError, StdResult, to_binary, Uint128};
 use crate::error::ContractError;
-use crate::handler::{change_owner, set_whitelist, swap_denom, update_pair_config, update_pair_max_spread, update_pair_status};
-use crate::helper::pair_key;
+use crate::handler::{add_pair_pool, remove_pair_pool, update_split_config, claim_ownership, drop_ownership_proposal, grant_role, pause, propose_new_owner, receive_cw20, revoke_role, unpause, set_rate_limit, set_whitelist, set_whitelist_batch, snapshot_prices, swap_denom, swap_denom_exact_out, swap_route, swap_route_reply, SWAP_ROUTE_REPLY_ID, update_fee_config, update_max_hops, update_oracle, update_pair_max_oracle_deviation, update_pair_config, update_pair_fee, update_pair_max_spread, update_pair_status};
 use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
-use crate::querier::{query_config, query_cumulative_prices, query_is_swap_whitelist, query_pair_config, query_reverse_simulation, query_simulation, query_swap_info};
-use crate::state::{Config, read_pair_config, store_config};
+use crate::querier::{query_all_pair_configs, query_best_route, query_caller_stats, query_caller_stats_list, query_rate_limit, query_volume_history, query_config, query_pairs_by_asset, query_cumulative_prices, query_is_swap_whitelist, query_pair_config, query_reverse_simulation, query_role_holders, query_whitelist, query_pair_simulation, query_quote, query_simulation_batch, query_swap_info, query_twap};
+use crate::state::{Config, DEFAULT_MAX_HOPS, DEFAULT_MAX_SPLIT_POOLS, migrate_pair_config, read_pair_config, store_config};
 
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -17,6 +16,14 @@ pub fn instantiate(
         deps.storage,
         &Config {
             owner: msg.owner,
+            max_hops: DEFAULT_MAX_HOPS,
+            paused: false,
+            paused_by: None,
+            fee_bps: 0,
+            fee_collector: None,
+            max_split_pools: DEFAULT_MAX_SPLIT_POOLS,
+            min_split_chunk: Uint128::zero(),
+            oracle: None,
         },
     )?;
 
@@ -32,15 +39,19 @@ pub fn execute(
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
     match msg {
-        ExecuteMsg::UpdatePairConfig { asset_infos, pair_address, max_spread, to }
+        ExecuteMsg::UpdatePairConfig { asset_infos, pair_address, max_spread, to, pair_type }
         => {
             // Validate input parameters before processing
             if asset_infos.len() != 2 {
                 return Err(ContractError::InvalidParameter);
             }
-            update_pair_config(deps, info, asset_infos, pair_address, max_spread, to)
+            update_pair_config(deps, info, asset_infos, pair_address, max_spread, to, pair_type.unwrap_or_default())
         }
-        ExecuteMsg::ChangeOwner { new_owner } => change_owner(deps, info, new_owner),
+        ExecuteMsg::ProposeNewOwner { owner, expires_in } => propose_new_owner(deps, env, info, owner, expires_in),
+        ExecuteMsg::DropOwnershipProposal {} => drop_ownership_proposal(deps, info),
+        ExecuteMsg::ClaimOwnership {} => claim_ownership(deps, env, info),
+        ExecuteMsg::GrantRole { role, address } => grant_role(deps, info, role, address),
+        ExecuteMsg::RevokeRole { role, address } => revoke_role(deps, info, role, address),
         ExecuteMsg::UpdatePairStatus { asset_infos, is_disabled } => {
             if asset_infos.len() != 2 {
                 return Err(ContractError::InvalidParameter);
@@ -54,43 +65,108 @@ pub fn execute(
             update_pair_max_spread(deps, info, asset_infos, max_spread)
         }
         ExecuteMsg::SetWhitelist { caller, is_whitelist } => set_whitelist(deps, info, caller, is_whitelist),
-        ExecuteMsg::SwapDenom { from_coin, target_denom, to_address } => swap_denom(deps, env, info, from_coin, target_denom, to_address),
+        ExecuteMsg::SetWhitelistBatch { entries } => set_whitelist_batch(deps, info, entries),
+        ExecuteMsg::UpdateMaxHops { max_hops } => update_max_hops(deps, info, max_hops),
+        ExecuteMsg::UpdateSplitConfig { max_pools, min_chunk } => update_split_config(deps, info, max_pools, min_chunk),
+        ExecuteMsg::AddPairPool { asset_infos, pool_address } => add_pair_pool(deps, info, asset_infos, pool_address),
+        ExecuteMsg::RemovePairPool { asset_infos, pool_address } => remove_pair_pool(deps, info, asset_infos, pool_address),
+        ExecuteMsg::UpdateOracle { oracle } => update_oracle(deps, info, oracle),
+        ExecuteMsg::UpdatePairMaxOracleDeviation { asset_infos, max_oracle_deviation } => {
+            update_pair_max_oracle_deviation(deps, info, asset_infos, max_oracle_deviation)
+        }
+        ExecuteMsg::UpdateFeeConfig { fee_bps, fee_collector } => update_fee_config(deps, info, fee_bps, fee_collector),
+        ExecuteMsg::UpdatePairFee { asset_infos, fee_bps } => update_pair_fee(deps, info, asset_infos, fee_bps),
+        ExecuteMsg::SetRateLimit { offer_asset, rate_limit } => set_rate_limit(deps, info, offer_asset, rate_limit),
+        ExecuteMsg::SnapshotPrices {} => snapshot_prices(deps, env),
+        ExecuteMsg::Pause {} => pause(deps, info),
+        ExecuteMsg::Unpause {} => unpause(deps, info),
+        ExecuteMsg::SwapDenom { from_coin, target_denom, to_address, auto_route, belief_price, max_spread, minimum_receive, split } => {
+            swap_denom(deps, env, info, from_coin, target_denom, to_address, auto_route, belief_price, max_spread, minimum_receive, split)
+        }
+        ExecuteMsg::SwapDenomExactOut { max_from_coin, target, to_address } => {
+            swap_denom_exact_out(deps, env, info, max_from_coin, target, to_address)
+        }
+        ExecuteMsg::SwapRoute { operations, minimum_receive, to_address } => swap_route(deps, env, info, operations, minimum_receive, to_address),
+        ExecuteMsg::Receive(msg) => receive_cw20(deps, env, info, msg),
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    match msg.id {
+        SWAP_ROUTE_REPLY_ID => {
+            let response = msg.result.into_result().map_err(StdError::generic_err)?;
+            swap_route_reply(deps, env, response)
+        }
+        id => Err(ContractError::InvalidReplyId { id }),
+    }
+}
+
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::QueryConfig {} => to_binary(&query_config(deps)?),
         QueryMsg::QuerySwapInfo { asset_infos } => to_binary(&query_swap_info(deps, asset_infos)?),
         QueryMsg::QueryIsSwapWhitelist { caller } => to_binary(&query_is_swap_whitelist(deps, caller)?),
+        QueryMsg::QueryWhitelist { start_after, limit } => to_binary(&query_whitelist(deps, start_after, limit)?),
         QueryMsg::QueryPairConfig { asset_infos } => {
             to_binary(&query_pair_config(deps, asset_infos)?)
         }
         QueryMsg::QuerySimulation { asset_infos, offer_asset } => {
-            let pair_key = pair_key(&asset_infos);
-            let pair_config = read_pair_config(deps.storage, &pair_key)?;
-            let contract_addr = pair_config.pair_address.clone().to_string();
-            to_binary(&query_simulation(&deps.querier, contract_addr, offer_asset)?)
+            to_binary(&query_pair_simulation(deps, asset_infos, offer_asset)?)
+        }
+        QueryMsg::QuerySimulationBatch { requests } => to_binary(&query_simulation_batch(deps, requests)?),
+        QueryMsg::QueryQuote { offer_asset, ask_asset_info } => {
+            to_binary(&query_quote(deps, offer_asset, ask_asset_info)?)
         }
         QueryMsg::QueryReverseSimulation { asset_infos, ask_asset } => {
-            let pair_key = pair_key(&asset_infos);
-            let pair_config = read_pair_config(deps.storage, &pair_key)?;
-            let contract_addr = pair_config.pair_address.clone().to_string();
-            to_binary(&query_reverse_simulation(&deps.querier, contract_addr, ask_asset)?)
+            let pair_config = read_pair_config(deps.storage, &asset_infos)?;
+            to_binary(&query_reverse_simulation(&deps.querier, &pair_config, ask_asset)?)
         }
         QueryMsg::QueryCumulativePrices { asset_infos } => {
-            let pair_key = pair_key(&asset_infos);
-            let pair_config = read_pair_config(deps.storage, &pair_key)?;
+            let pair_config = read_pair_config(deps.storage, &asset_infos)?;
             let contract_addr = pair_config.pair_address.clone().to_string();
             to_binary(&query_cumulative_prices(&deps.querier, contract_addr)?)
         }
+        QueryMsg::QueryTwap { asset_infos, window_seconds } => {
+            to_binary(&query_twap(deps, env, asset_infos, window_seconds)?)
+        }
+        QueryMsg::QueryRoleHolders { role, start_after, limit } => {
+            to_binary(&query_role_holders(deps, role, start_after, limit)?)
+        }
+        QueryMsg::QueryBestRoute { offer_asset, ask_asset_info } => {
+            to_binary(&query_best_route(deps, offer_asset, ask_asset_info)?)
+        }
+        QueryMsg::QueryPairsByAsset { asset_info, start_after, limit } => {
+            to_binary(&query_pairs_by_asset(deps, asset_info, start_after, limit)?)
+        }
+        QueryMsg::QueryAllPairConfigs { start_after, limit } => {
+            to_binary(&query_all_pair_configs(deps, start_after, limit)?)
+        }
+        QueryMsg::QueryRateLimit { offer_asset } => to_binary(&query_rate_limit(deps, offer_asset)?),
+        QueryMsg::QueryVolumeHistory { asset_infos, from, to, granularity } => {
+            to_binary(&query_volume_history(deps, asset_infos, from, to, granularity)?)
+        }
+        QueryMsg::QueryCallerStats { caller, asset_infos } => {
+            to_binary(&query_caller_stats(deps, caller, asset_infos)?)
+        }
+        QueryMsg::QueryCallerStatsList { caller, start_after, limit } => {
+            to_binary(&query_caller_stats_list(deps, caller, start_after, limit)?)
+        }
     }
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, _msg: MigrateMsg) -> StdResult<Response> {
-    Ok(Response::default())
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let mut migrated_pairs = 0u32;
+    for asset_infos in msg.pairs.iter() {
+        if migrate_pair_config(deps.storage, asset_infos)? {
+            migrated_pairs += 1;
+        }
+    }
+    Ok(Response::new()
+        .add_attribute("action", "migrate")
+        .add_attribute("migrated_pairs", migrated_pairs.to_string()))
 }
 
 #[cfg(test)]
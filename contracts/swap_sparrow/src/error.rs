@@ -1,38 +1,100 @@
-use cosmwasm_std::{OverflowError, StdError};
-use thiserror::Error;
-
-#[derive(Error, Debug, PartialEq)]
-pub enum ContractError {
-    #[error("{0}")]
-    Std(#[from] StdError),
-
-    #[error("{0}")]
-    OverflowError(#[from] OverflowError),
-
-    #[error("Unauthorized")]
-    Unauthorized {},
-
-    #[error("Pair config disabled")]
-    PairConfigDisabled {},
-
-    #[error("Balance not enough")]
-    BalanceNotEnough {},
-
-    #[error("Pair not found")]
-    PairNotFound {},
-
-    #[error("Unable to receive msg")]
-    UnableToReceiveMsg {},
-
-    #[error("Invalid denom")]
-    InvalidDenom,
-
-    #[error("Invalid parameter")]
-    InvalidParameter,
-
-    #[error("Invalid owner")]
-    InvalidOwner,
-
-    #[error("Invalid amount")]
-    InvalidAmount,
-}
+use cosmwasm_std::{Decimal, Decimal256, OverflowError, StdError, Uint128};
+use thiserror::Error;
+use crate::state::{PairType, Role};
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ContractError {
+    #[error("{0}")]
+    Std(#[from] StdError),
+
+    #[error("{0}")]
+    OverflowError(#[from] OverflowError),
+
+    #[error("Unauthorized")]
+    Unauthorized {},
+
+    #[error("Unauthorized: missing role {role}")]
+    MissingRole { role: Role },
+
+    #[error("Swaps are paused")]
+    Paused {},
+
+    #[error("Pair {pair} is disabled")]
+    PairConfigDisabled { pair: String },
+
+    #[error("Balance not enough")]
+    BalanceNotEnough {},
+
+    #[error("Pair {pair} not found")]
+    PairNotFound { pair: String },
+
+    #[error("No swap recorded for pair {pair}")]
+    SwapInfoNotFound { pair: String },
+
+    #[error("Unable to receive msg")]
+    UnableToReceiveMsg {},
+
+    #[error("Invalid denom")]
+    InvalidDenom,
+
+    #[error("Invalid parameter")]
+    InvalidParameter,
+
+    #[error("Invalid owner")]
+    InvalidOwner,
+
+    #[error("Invalid amount")]
+    InvalidAmount,
+
+    #[error("Insufficient funds; {denom} required: {required}, provided: {provided}")]
+    InsufficientFunds { denom: String, required: Uint128, provided: Uint128 },
+
+    #[error("Ownership proposal not found")]
+    OwnershipProposalNotFound {},
+
+    #[error("Ownership proposal expired")]
+    OwnershipProposalExpired {},
+
+    #[error("Invalid swap operations")]
+    InvalidSwapOperations {},
+
+    #[error("Assertion failed; minimum receive amount: {minimum_receive}, swap amount: {receive}")]
+    AssertionMinimumReceive {
+        receive: Uint128,
+        minimum_receive: Uint128,
+    },
+
+    #[error("Max input exceeded; required: {required}, max: {max}")]
+    MaxInputExceeded { required: Uint128, max: Uint128 },
+
+    #[error("Swap amount limit exceeded; amount: {amount}, max: {max}")]
+    SwapAmountLimitExceeded { amount: Uint128, max: Uint128 },
+
+    #[error("Caller volume limit exceeded; volume in window: {volume}, max: {max}")]
+    CallerVolumeLimitExceeded { volume: Uint128, max: Uint128 },
+
+    #[error("Pair volume limit exceeded; volume in window: {volume}, max: {max}")]
+    PairVolumeLimitExceeded { volume: Uint128, max: Uint128 },
+
+    #[error("Oracle price deviation of pair {pair}; deviation: {deviation}, max: {max}")]
+    OraclePriceDeviation { pair: String, deviation: Decimal256, max: Decimal },
+
+    #[error("Unsupported pair type {pair_type}")]
+    UnsupportedPairType { pair_type: PairType },
+
+    #[error("Invalid pair {pair}: {reason}")]
+    InvalidPair { pair: String, reason: String },
+
+    #[error("Invalid reply id: {id}")]
+    InvalidReplyId { id: u64 },
+}
+
+/// Lets the queries, which return a [`StdError`], share the state accessors returning a [`ContractError`]
+impl From<ContractError> for StdError {
+    fn from(err: ContractError) -> Self {
+        match err {
+            ContractError::Std(err) => err,
+            err => StdError::generic_err(err.to_string()),
+        }
+    }
+}
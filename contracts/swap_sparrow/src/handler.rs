@@ -1,10 +1,76 @@
-use cosmwasm_std::{Addr, Coin, CosmosMsg, Decimal, DepsMut, Env, MessageInfo, Response, StdError, SubMsg, to_binary, WasmMsg};
+use std::str::FromStr;
+use cosmwasm_std::{Addr, BankMsg, Coin, Decimal, Decimal256, Deps, DepsMut, Env, Event, from_binary, MessageInfo, Order, QuerierWrapper, Response, StdError, StdResult, Storage, SubMsg, SubMsgResponse, Uint128};
+use cw20::Cw20ReceiveMsg;
 use crate::error::ContractError;
-use crate::helper::{Asset, AssetInfo, pair_key};
-use crate::msg::{SwapMsg};
-use crate::querier::query_simulation;
-use crate::state::{Config, is_address_in_whitelist, PairConfig, read_config, read_pair_config, read_swap_info_default_zero, store_config, store_pair_configs, store_swap_infos, store_swap_whitelist};
+use crate::helper::{Asset, AssetInfo, pair_key, pair_name, pair_swap_msg};
+use crate::msg::{Cw20HookMsg, SwapOperation};
+use crate::querier::{query_cumulative_prices, query_best_route, query_oracle_price, query_pair_info, query_reverse_simulation, query_simulation, query_split_offers};
+use crate::state::{MAX_HOPS, PriceSnapshot, store_price_snapshot, add_swap_volume, PairType, RateLimit, read_caller_volume, read_pair_volume, read_rate_limit, remove_rate_limit, store_caller_volume, store_pair_volume, store_rate_limit, CallerStats, Config, has_role, MAX_FEE_BPS, read_caller_stats, store_caller_stats, is_address_in_whitelist, remove_role, Role, store_role, MAX_PROPOSAL_TTL, OwnershipProposal, read_ownership_proposal, remove_ownership_proposal, store_ownership_proposal, pair_configs, PairConfig, read_config, read_pair_config, read_swap_info_default_zero, read_swap_route_state, remove_swap_route_state, store_config, store_pair_configs, store_swap_infos, store_swap_route_state, store_swap_whitelist, SwapRouteState};
 
+/// Reply id of a single hop submessage dispatched by [`swap_route`].
+pub const SWAP_ROUTE_REPLY_ID: u64 = 1;
+
+
+/**
+ * Check that the sender holds the role. The owner and the admins hold every role
+ */
+fn assert_role(deps: Deps, sender: &Addr, role: Role) -> Result<(), ContractError> {
+    assert_any_role(deps, sender, &[role])
+}
+
+/**
+ * Check that the sender holds one of the roles, the error names the first one
+ */
+fn assert_any_role(deps: Deps, sender: &Addr, roles: &[Role]) -> Result<(), ContractError> {
+    let config = read_config(deps.storage)?;
+    if *sender == config.owner || has_role(deps.storage, Role::Admin, sender)? {
+        return Ok(());
+    }
+    for role in roles {
+        if has_role(deps.storage, *role, sender)? {
+            return Ok(());
+        }
+    }
+    Err(ContractError::MissingRole { role: roles[0] })
+}
+
+/**
+ * Grant a role to an address, only the owner can manage roles
+ */
+pub fn grant_role(deps: DepsMut, info: MessageInfo, role: Role, address: String) -> Result<Response, ContractError> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let address = deps.api.addr_validate(&address)?;
+    store_role(deps.storage, role, &address)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "grant_role"),
+        ("role", role.as_str()),
+        ("address", address.as_str()),
+    ]))
+}
+
+/**
+ * Revoke a role from an address
+ */
+pub fn revoke_role(deps: DepsMut, info: MessageInfo, role: Role, address: String) -> Result<Response, ContractError> {
+    let config = read_config(deps.storage)?;
+    if info.sender != config.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let address = deps.api.addr_validate(&address)?;
+    remove_role(deps.storage, role, &address);
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "revoke_role"),
+        ("role", role.as_str()),
+        ("address", address.as_str()),
+    ]))
+}
 
 /**
  * Update the config of the contract
@@ -13,17 +79,32 @@ use crate::state::{Config, is_address_in_whitelist, PairConfig, read_config, rea
 pub fn update_pair_config(deps: DepsMut, info: MessageInfo,
                           asset_infos: [AssetInfo; 2],
                           pair_address: Addr, max_spread: Option<Decimal>,
-                          to: Option<Addr>) -> Result<Response, ContractError> {
-    let config = read_config(deps.storage)?;
-    if info.sender != config.owner {
-        return Err(ContractError::Unauthorized {});
+                          to: Option<Addr>, pair_type: PairType) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::PairManager)?;
+    if pair_type == PairType::NativeDex {
+        return Err(ContractError::UnsupportedPairType { pair_type });
     }
+    let pair_address = deps.api.addr_validate(pair_address.as_str())?;
+    assert_pair_assets(deps.as_ref(), &pair_address, &asset_infos, pair_type)?;
 
+    // the settings of a pair registered again are kept unless overridden,
+    // its pools were validated against its type and are dropped when the type changes
+    let pair_key = pair_key(&asset_infos);
+    let existing = pair_configs().may_load(deps.storage, &pair_key)?;
     let mut pair_config = PairConfig {
+        asset_infos: asset_infos.clone(),
         pair_address: pair_address.clone(),
-        is_disabled: false,
-        max_spread: None,
-        to: None,
+        is_disabled: existing.as_ref().map(|existing| existing.is_disabled).unwrap_or(false),
+        max_spread: existing.as_ref().and_then(|existing| existing.max_spread),
+        to: existing.as_ref().and_then(|existing| existing.to.clone()),
+        fee_bps: existing.as_ref().and_then(|existing| existing.fee_bps),
+        pair_type,
+        pools: existing
+            .as_ref()
+            .filter(|existing| existing.pair_type == pair_type)
+            .map(|existing| existing.pools.iter().filter(|pool| **pool != pair_address).cloned().collect())
+            .unwrap_or_default(),
+        max_oracle_deviation: existing.as_ref().and_then(|existing| existing.max_oracle_deviation),
     };
 
     if let Some(max_spread) = max_spread {
@@ -33,43 +114,120 @@ pub fn update_pair_config(deps: DepsMut, info: MessageInfo,
         pair_config.to = Some(to);
     }
 
-    let pair_key = pair_key(&asset_infos);
     store_pair_configs(deps.storage, &pair_key, &pair_config)?;
 
     Ok(Response::new().add_attributes(vec![
         ("action", "update_pair_config"),
         ("pair_address", pair_address.as_str()),
-        ("max_spread", max_spread.unwrap_or_default().to_string().as_str()), ]))
+        ("max_spread", max_spread.unwrap_or_default().to_string().as_str()),
+        ("pair_type", pair_type.as_str()), ]))
 }
 
 
 /**
- * Change the owner of the contract
+ * Check that the pair contract trades the two assets, the stable pools may trade more assets
  */
-pub fn change_owner(deps: DepsMut, info: MessageInfo, new_owner: Addr) -> Result<Response, ContractError> {
-    let mut config: Config = read_config(deps.storage)?;
+fn assert_pair_assets(deps: Deps, pair_address: &Addr, asset_infos: &[AssetInfo; 2],
+                      pair_type: PairType) -> Result<(), ContractError> {
+    let invalid_pair = |reason: String| ContractError::InvalidPair { pair: pair_address.to_string(), reason };
+    for asset_info in asset_infos.iter() {
+        asset_info.check(deps.api).map_err(|e| invalid_pair(format!("invalid asset {}: {}", asset_info, e)))?;
+    }
+    if asset_infos[0].equal(&asset_infos[1]) {
+        return Err(invalid_pair(format!("both assets are {}", asset_infos[0])));
+    }
+
+    let pair_info = query_pair_info(&deps.querier, pair_address.to_string())
+        .map_err(|e| invalid_pair(format!("pair info query failed: {}", e)))?;
+    let trades = |asset_info: &AssetInfo| pair_info.asset_infos.iter().any(|a| a.equal(asset_info));
+    let multi_asset = pair_type == PairType::AstroportStable;
+    if (!multi_asset && pair_info.asset_infos.len() != 2) || !asset_infos.iter().all(trades) {
+        let found: Vec<String> = pair_info.asset_infos.iter().map(|a| a.to_string()).collect();
+        return Err(invalid_pair(format!("expected assets {}, {} but the pair trades {}",
+                                        asset_infos[0], asset_infos[1], found.join(", "))));
+    }
+    Ok(())
+}
+
+/**
+ * Propose a new owner of the contract, the proposal has to be claimed by the new owner before it expires
+ */
+pub fn propose_new_owner(deps: DepsMut, env: Env, info: MessageInfo, owner: String, expires_in: u64) -> Result<Response, ContractError> {
+    let config: Config = read_config(deps.storage)?;
     if info.sender != config.owner {
         return Err(ContractError::Unauthorized {});
     }
 
-    config.owner = new_owner.clone();
-    store_config(deps.storage, &config)?;
+    let new_owner = deps.api.addr_validate(&owner)?;
+    if new_owner == config.owner {
+        return Err(ContractError::InvalidOwner);
+    }
+    if expires_in == 0 || expires_in > MAX_PROPOSAL_TTL {
+        return Err(ContractError::InvalidParameter);
+    }
+
+    let proposal = OwnershipProposal {
+        owner: new_owner.clone(),
+        ttl: env.block.time.seconds() + expires_in,
+    };
+    store_ownership_proposal(deps.storage, &proposal)?;
 
     Ok(Response::new().add_attributes(vec![
-        ("action", "change_owner"),
+        ("action", "propose_new_owner"),
         ("new_owner", new_owner.as_str()),
+        ("ttl", proposal.ttl.to_string().as_str()),
     ]))
 }
 
-pub fn update_pair_status(deps: DepsMut, info: MessageInfo,
-                          asset_infos: [AssetInfo; 2], is_disabled: bool) -> Result<Response, ContractError> {
-    let config = read_config(deps.storage)?;
+/**
+ * Remove the pending ownership proposal
+ */
+pub fn drop_ownership_proposal(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    let config: Config = read_config(deps.storage)?;
     if info.sender != config.owner {
         return Err(ContractError::Unauthorized {});
     }
 
+    remove_ownership_proposal(deps.storage);
+
+    Ok(Response::new().add_attribute("action", "drop_ownership_proposal"))
+}
+
+/**
+ * Claim the ownership of the contract as the proposed owner
+ */
+pub fn claim_ownership(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, ContractError> {
+    let proposal = read_ownership_proposal(deps.storage)?
+        .ok_or(ContractError::OwnershipProposalNotFound {})?;
+    if info.sender != proposal.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    if env.block.time.seconds() > proposal.ttl {
+        return Err(ContractError::OwnershipProposalExpired {});
+    }
+
+    let mut config: Config = read_config(deps.storage)?;
+    config.owner = proposal.owner.clone();
+    store_config(deps.storage, &config)?;
+    remove_ownership_proposal(deps.storage);
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "claim_ownership"),
+        ("new_owner", proposal.owner.as_str()),
+    ]))
+}
+
+pub fn update_pair_status(deps: DepsMut, info: MessageInfo,
+                          asset_infos: [AssetInfo; 2], is_disabled: bool) -> Result<Response, ContractError> {
+    // guardians may only disable a pair
+    if is_disabled {
+        assert_any_role(deps.as_ref(), &info.sender, &[Role::Guardian, Role::PairManager])?;
+    } else {
+        assert_role(deps.as_ref(), &info.sender, Role::PairManager)?;
+    }
+
     let pair_key = pair_key(&asset_infos);
-    let mut pair_config = read_pair_config(deps.storage, &pair_key)?;
+    let mut pair_config = read_pair_config(deps.storage, &asset_infos)?;
     pair_config.is_disabled = is_disabled;
 
     store_pair_configs(deps.storage, &pair_key, &pair_config)?;
@@ -83,13 +241,10 @@ pub fn update_pair_status(deps: DepsMut, info: MessageInfo,
 
 pub fn update_pair_max_spread(deps: DepsMut, info: MessageInfo,
                               asset_infos: [AssetInfo; 2], max_spread: Decimal) -> Result<Response, ContractError> {
-    let config = read_config(deps.storage)?;
-    if info.sender != config.owner {
-        return Err(ContractError::Unauthorized {});
-    }
+    assert_role(deps.as_ref(), &info.sender, Role::PairManager)?;
 
     let pair_key = pair_key(&asset_infos);
-    let mut pair_config = read_pair_config(deps.storage, &pair_key)?;
+    let mut pair_config = read_pair_config(deps.storage, &asset_infos)?;
     pair_config.max_spread = Some(max_spread);
 
     store_pair_configs(deps.storage, &pair_key, &pair_config)?;
@@ -102,21 +257,383 @@ pub fn update_pair_max_spread(deps: DepsMut, info: MessageInfo,
 }
 
 pub fn set_whitelist(deps: DepsMut, info: MessageInfo, caller: Addr, is_whitelist: bool) -> Result<Response, ContractError> {
-    let config = read_config(deps.storage)?;
-    if info.sender != config.owner {
-        return Err(ContractError::Unauthorized {});
+    assert_role(deps.as_ref(), &info.sender, Role::WhitelistManager)?;
+
+    store_swap_whitelist(deps.storage, caller.clone(), is_whitelist)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_whitelist")
+        .add_event(whitelist_event(&caller, is_whitelist)))
+}
+
+/**
+ * Set the whitelist status of several callers at once
+ */
+pub fn set_whitelist_batch(deps: DepsMut, info: MessageInfo, entries: Vec<(Addr, bool)>) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::WhitelistManager)?;
+    if entries.is_empty() {
+        return Err(ContractError::InvalidParameter);
+    }
+
+    let mut res = Response::new().add_attributes(vec![
+        ("action", "set_whitelist_batch"),
+        ("count", entries.len().to_string().as_str()),
+    ]);
+    for (caller, is_whitelist) in entries {
+        let caller = deps.api.addr_validate(caller.as_str())?;
+        store_swap_whitelist(deps.storage, caller.clone(), is_whitelist)?;
+        res = res.add_event(whitelist_event(&caller, is_whitelist));
+    }
+
+    Ok(res)
+}
+
+fn whitelist_event(caller: &Addr, is_whitelist: bool) -> Event {
+    Event::new("swap_whitelist")
+        .add_attribute("caller", caller.as_str())
+        .add_attribute("is_whitelist", is_whitelist.to_string())
+}
+
+/**
+ * Update the max number of hops of a swap route
+ */
+pub fn update_max_hops(deps: DepsMut, info: MessageInfo, max_hops: u32) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::Admin)?;
+    if max_hops == 0 || max_hops > MAX_HOPS {
+        return Err(ContractError::InvalidParameter);
+    }
+
+    let mut config = read_config(deps.storage)?;
+    config.max_hops = max_hops;
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_max_hops"),
+        ("max_hops", max_hops.to_string().as_str()),
+    ]))
+}
+
+/**
+ * Update the max number of pools of a split swap and the min amount offered to each pool
+ */
+pub fn update_split_config(deps: DepsMut, info: MessageInfo, max_pools: u32, min_chunk: Uint128) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::Admin)?;
+    if max_pools == 0 {
+        return Err(ContractError::InvalidParameter);
+    }
+
+    let mut config = read_config(deps.storage)?;
+    config.max_split_pools = max_pools;
+    config.min_split_chunk = min_chunk;
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_split_config"),
+        ("max_pools", max_pools.to_string().as_str()),
+        ("min_chunk", min_chunk.to_string().as_str()),
+    ]))
+}
+
+/**
+ * Update the oracle the prices of the pairs are checked against
+ */
+pub fn update_oracle(deps: DepsMut, info: MessageInfo, oracle: Option<String>) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::Admin)?;
+
+    let mut config = read_config(deps.storage)?;
+    config.oracle = oracle.map(|oracle| deps.api.addr_validate(&oracle)).transpose()?;
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_oracle"),
+        ("oracle", config.oracle.map(|addr| addr.to_string()).unwrap_or_default().as_str()),
+    ]))
+}
+
+/**
+ * Update the router fee and its collector
+ */
+pub fn update_fee_config(deps: DepsMut, info: MessageInfo, fee_bps: u16, fee_collector: Option<String>) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::Admin)?;
+
+    let mut config = read_config(deps.storage)?;
+    if let Some(fee_collector) = fee_collector {
+        config.fee_collector = Some(deps.api.addr_validate(&fee_collector)?);
+    }
+    if fee_bps > MAX_FEE_BPS || (fee_bps > 0 && config.fee_collector.is_none()) {
+        return Err(ContractError::InvalidParameter);
+    }
+    config.fee_bps = fee_bps;
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_fee_config"),
+        ("fee_bps", fee_bps.to_string().as_str()),
+        ("fee_collector", config.fee_collector.map(|addr| addr.to_string()).unwrap_or_default().as_str()),
+    ]))
+}
+
+/**
+ * Update the max deviation of the simulated price of a pair from the oracle price
+ */
+pub fn update_pair_max_oracle_deviation(deps: DepsMut, info: MessageInfo, asset_infos: [AssetInfo; 2],
+                                        max_oracle_deviation: Option<Decimal>) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::PairManager)?;
+
+    let pair_key = pair_key(&asset_infos);
+    let mut pair_config = read_pair_config(deps.storage, &asset_infos)?;
+    pair_config.max_oracle_deviation = max_oracle_deviation;
+    store_pair_configs(deps.storage, &pair_key, &pair_config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_pair_max_oracle_deviation"),
+        ("pair_address", pair_config.pair_address.as_str()),
+        ("max_oracle_deviation", max_oracle_deviation.map(|d| d.to_string()).unwrap_or_default().as_str()),
+    ]))
+}
+
+/**
+ * Override the router fee of a pair
+ */
+pub fn update_pair_fee(deps: DepsMut, info: MessageInfo,
+                       asset_infos: [AssetInfo; 2], fee_bps: Option<u16>) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::PairManager)?;
+    if matches!(fee_bps, Some(fee_bps) if fee_bps > MAX_FEE_BPS) {
+        return Err(ContractError::InvalidParameter);
+    }
+
+    let pair_key = pair_key(&asset_infos);
+    let mut pair_config = read_pair_config(deps.storage, &asset_infos)?;
+    pair_config.fee_bps = fee_bps;
+    store_pair_configs(deps.storage, &pair_key, &pair_config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "update_pair_fee"),
+        ("pair_address", pair_config.pair_address.as_str()),
+        ("fee_bps", fee_bps.map(|fee_bps| fee_bps.to_string()).unwrap_or_default().as_str()),
+    ]))
+}
+
+/**
+ * Register an additional pool of a pair, the pool has the type of the pair
+ */
+pub fn add_pair_pool(deps: DepsMut, info: MessageInfo,
+                     asset_infos: [AssetInfo; 2], pool_address: String) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::PairManager)?;
+    let pool_address = deps.api.addr_validate(&pool_address)?;
+
+    let pair_key = pair_key(&asset_infos);
+    let mut pair_config = read_pair_config(deps.storage, &asset_infos)?;
+    if pair_config.pool_addresses().contains(&pool_address) {
+        return Err(ContractError::InvalidPair {
+            pair: pool_address.to_string(),
+            reason: "pool already registered".to_string(),
+        });
+    }
+    assert_pair_assets(deps.as_ref(), &pool_address, &pair_config.asset_infos, pair_config.pair_type)?;
+    pair_config.pools.push(pool_address.clone());
+    store_pair_configs(deps.storage, &pair_key, &pair_config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "add_pair_pool"),
+        ("pair_address", pair_config.pair_address.as_str()),
+        ("pool_address", pool_address.as_str()),
+    ]))
+}
+
+/**
+ * Remove an additional pool of a pair
+ */
+pub fn remove_pair_pool(deps: DepsMut, info: MessageInfo,
+                        asset_infos: [AssetInfo; 2], pool_address: String) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::PairManager)?;
+
+    let pair_key = pair_key(&asset_infos);
+    let mut pair_config = read_pair_config(deps.storage, &asset_infos)?;
+    if !pair_config.pools.iter().any(|pool| pool.as_str() == pool_address) {
+        return Err(ContractError::InvalidPair {
+            pair: pool_address,
+            reason: "pool not registered".to_string(),
+        });
     }
+    pair_config.pools.retain(|pool| pool.as_str() != pool_address);
+    store_pair_configs(deps.storage, &pair_key, &pair_config)?;
 
-    store_swap_whitelist(deps.storage, caller, is_whitelist)?;
+    Ok(Response::new().add_attributes(vec![
+        ("action", "remove_pair_pool"),
+        ("pair_address", pair_config.pair_address.as_str()),
+        ("pool_address", pool_address.as_str()),
+    ]))
+}
 
-    Ok(Response::default())
+/**
+ * Snapshot the cumulative prices of every enabled pair, the pairs without cumulative prices are skipped
+ */
+pub fn snapshot_prices(deps: DepsMut, env: Env) -> Result<Response, ContractError> {
+    let enabled_pairs = pair_configs()
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<Vec<(Vec<u8>, PairConfig)>>>()?
+        .into_iter()
+        .filter(|(_, pair_config)| !pair_config.is_disabled);
+    let mut count = 0u32;
+    for (pair_key, pair_config) in enabled_pairs {
+        if record_price_snapshot(deps.storage, &deps.querier, &env, &pair_key, &pair_config)? {
+            count += 1;
+        }
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "snapshot_prices"),
+        ("snapshots", count.to_string().as_str()),
+    ]))
+}
+
+/**
+ * Snapshot the cumulative prices of a pair, returns false when the pair does not report them
+ */
+fn record_price_snapshot(storage: &mut dyn Storage, querier: &QuerierWrapper, env: &Env, pair_key: &[u8],
+                         pair_config: &PairConfig) -> StdResult<bool> {
+    let prices = match query_cumulative_prices(querier, pair_config.pair_address.to_string()) {
+        Ok(prices) => prices,
+        Err(_) => return Ok(false),
+    };
+    store_price_snapshot(storage, pair_key, &PriceSnapshot {
+        time: env.block.time.seconds(),
+        price0_cumulative: prices.price0_cumulative_last,
+        price1_cumulative: prices.price1_cumulative_last,
+    })?;
+    Ok(true)
+}
+
+/**
+ * Pause all swaps, guardians can pause in an emergency
+ */
+pub fn pause(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::Guardian)?;
+
+    let mut config = read_config(deps.storage)?;
+    config.paused = true;
+    config.paused_by = Some(info.sender.clone());
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "pause"),
+        ("paused_by", info.sender.as_str()),
+    ]))
+}
+
+/**
+ * Resume swaps
+ */
+pub fn unpause(deps: DepsMut, info: MessageInfo) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::Admin)?;
+
+    let mut config = read_config(deps.storage)?;
+    config.paused = false;
+    config.paused_by = None;
+    store_config(deps.storage, &config)?;
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "unpause"),
+        ("unpaused_by", info.sender.as_str()),
+    ]))
+}
+
+/**
+ * Set the limits on the swaps offering an asset
+ */
+pub fn set_rate_limit(deps: DepsMut, info: MessageInfo, offer_asset: AssetInfo,
+                      rate_limit: Option<RateLimit>) -> Result<Response, ContractError> {
+    assert_role(deps.as_ref(), &info.sender, Role::Admin)?;
+    offer_asset.check(deps.api)?;
+
+    match &rate_limit {
+        Some(rate_limit) => {
+            if rate_limit.window_seconds == 0 {
+                return Err(ContractError::InvalidParameter);
+            }
+            store_rate_limit(deps.storage, &offer_asset, rate_limit)?;
+        }
+        None => remove_rate_limit(deps.storage, &offer_asset),
+    }
+
+    Ok(Response::new().add_attributes(vec![
+        ("action", "set_rate_limit"),
+        ("offer_asset", offer_asset.to_string().as_str()),
+        ("enabled", rate_limit.is_some().to_string().as_str()),
+    ]))
+}
+
+/**
+ * Check the hop of a swap against the limits of its offer asset and add it to the volumes of the caller and the pair
+ */
+fn apply_rate_limit(storage: &mut dyn Storage, env: &Env, sender: &Addr, operation: &SwapOperation,
+                    amount: Uint128) -> Result<(), ContractError> {
+    let offer_asset = &operation.offer_asset_info;
+    let rate_limit = match read_rate_limit(storage, offer_asset)? {
+        Some(rate_limit) => rate_limit,
+        None => return Ok(()),
+    };
+    if let Some(max) = rate_limit.max_swap_amount {
+        if amount > max {
+            return Err(ContractError::SwapAmountLimitExceeded { amount, max });
+        }
+    }
+
+    let now = env.block.time.seconds();
+    let window_seconds = rate_limit.window_seconds;
+
+    let mut caller_volume = read_caller_volume(storage, sender, offer_asset)?;
+    caller_volume.roll(now, window_seconds);
+    caller_volume.current += amount;
+    if let Some(max) = rate_limit.max_caller_volume {
+        let volume = caller_volume.volume(now, window_seconds);
+        if volume > max {
+            return Err(ContractError::CallerVolumeLimitExceeded { volume, max });
+        }
+    }
+    store_caller_volume(storage, sender, offer_asset, &caller_volume)?;
+
+    let pair_key = pair_key(&[operation.offer_asset_info.clone(), operation.ask_asset_info.clone()]);
+    let mut pair_volume = read_pair_volume(storage, &pair_key, offer_asset)?;
+    pair_volume.roll(now, window_seconds);
+    pair_volume.current += amount;
+    if let Some(max) = rate_limit.max_pair_volume {
+        let volume = pair_volume.volume(now, window_seconds);
+        if volume > max {
+            return Err(ContractError::PairVolumeLimitExceeded { volume, max });
+        }
+    }
+    store_pair_volume(storage, &pair_key, offer_asset, &pair_volume)?;
+    Ok(())
+}
+
+fn assert_not_paused(deps: Deps) -> Result<(), ContractError> {
+    if read_config(deps.storage)?.paused {
+        return Err(ContractError::Paused {});
+    }
+    Ok(())
+}
+
+/**
+ * The max spread of a swap, the requested one can not exceed the max spread of the pair
+ */
+fn clamp_max_spread(requested: Option<Decimal>, pair_max_spread: Option<Decimal>) -> Option<Decimal> {
+    match (requested, pair_max_spread) {
+        (Some(requested), Some(pair_max_spread)) => Some(requested.min(pair_max_spread)),
+        (requested, pair_max_spread) => requested.or(pair_max_spread),
+    }
 }
 
 /**
 * U => A=>B=>SWAP=>B=>A
  * Swap the coin
  */
-pub fn swap_denom(deps: DepsMut, _env: Env, info: MessageInfo, from_coin: Coin, target_denom: String, to_address: Option<String>) -> Result<Response, ContractError> {
+#[allow(clippy::too_many_arguments)]
+pub fn swap_denom(deps: DepsMut, env: Env, info: MessageInfo, from_coin: Coin, target_denom: String, to_address: Option<String>,
+                  auto_route: Option<bool>, belief_price: Option<Decimal>, max_spread: Option<Decimal>,
+                  minimum_receive: Option<Uint128>, split: Option<bool>) -> Result<Response, ContractError> {
+    assert_not_paused(deps.as_ref())?;
     let sender = info.sender.clone();
     // check wihitelist
     if !is_address_in_whitelist(deps.storage, sender.clone())? {
@@ -129,83 +646,610 @@ pub fn swap_denom(deps: DepsMut, _env: Env, info: MessageInfo, from_coin: Coin,
         return Err(ContractError::InvalidAmount {});
     }
 
+    // only `from_coin` is swapped, the surplus and the other coins are refunded to the sender
+    assert_funds(&info.funds, &from_coin)?;
+    let refund = refund_coins(&info.funds, &from_coin);
+    let payment = &from_coin;
+
+    let split = split.unwrap_or(false);
+    let (operations, to) = if auto_route.unwrap_or(false) {
+        if belief_price.is_some() || split {
+            return Err(ContractError::InvalidParameter {});
+        }
+        let best_route = query_best_route(deps.as_ref(), Asset {
+            info: AssetInfo::NativeToken { denom: from_coin.denom.clone() },
+            amount: payment.amount,
+        }, AssetInfo::NativeToken { denom: target_denom.clone() })?;
+        validate_swap_operations(deps.as_ref(), &best_route.operations)?;
+        let to = match to_address {
+            Some(to_address) => deps.api.addr_validate(&to_address)?,
+            None => sender.clone(),
+        };
+        (best_route.operations, to)
+    } else {
+        let asset_infos = [
+            AssetInfo::NativeToken {
+                denom: from_coin.denom.clone(),
+            },
+            AssetInfo::NativeToken {
+                denom: target_denom.clone(),
+            },
+        ];
+        let pair_config = read_pair_config(deps.storage, &asset_infos)?;
+        if pair_config.is_disabled {
+            return Err(ContractError::PairConfigDisabled { pair: pair_name(&asset_infos) });
+        }
+
+        // the router receives the output, the reply accounts what was actually delivered
+        let operations = vec![SwapOperation {
+            offer_asset_info: asset_infos[0].clone(),
+            ask_asset_info: asset_infos[1].clone(),
+        }];
+        let to = match to_address {
+            Some(to_address) => deps.api.addr_validate(&to_address)?,
+            None => pair_config.to.unwrap_or(sender.clone()),
+        };
+        (operations, to)
+    };
+
+    apply_rate_limit(deps.storage, &env, &sender, &operations[0], payment.amount)?;
+
+    // the router fee is deducted from the offer amount, with the fee of the first pair
+    let config = read_config(deps.storage)?;
+    let first_pair_key = pair_key(&operations[0].asset_infos());
+    let first_pair_config = read_pair_config(deps.storage, &operations[0].asset_infos())?;
+    let fee_amount = config.fee_amount(&first_pair_config, payment.amount);
+    let offer_amount = payment.amount.checked_sub(fee_amount)?;
+    if offer_amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+    let mut res = add_refund(Response::new(), &sender, refund.clone());
+
+    // a split swap is divided across the pools of the direct pair with the best simulated returns
+    let split_offers = if split {
+        let split_offers = query_split_offers(&deps.querier, &first_pair_config, config.max_split_pools,
+                                              config.min_split_chunk, Asset {
+                                                  info: operations[0].offer_asset_info.clone(),
+                                                  amount: offer_amount,
+                                              })?;
+        res = res.add_attribute("split_offers", split_offers
+            .iter()
+            .map(|(pool, amount)| format!("{}:{}", pool, amount))
+            .collect::<Vec<String>>()
+            .join(","));
+        split_offers
+    } else {
+        vec![]
+    };
+    res = charge_fee(deps.storage, res, &config, &first_pair_key, Asset {
+        info: operations[0].offer_asset_info.clone(),
+        amount: fee_amount,
+    })?;
+
+    let route_res = start_swap_route(deps, env, sender, to, operations, offer_amount, minimum_receive, belief_price,
+                                     max_spread, split_offers, &refund)?;
+    Ok(res
+        .add_submessages(route_res.messages)
+        .add_attributes(route_res.attributes)
+        .add_attributes(vec![
+            ("from_coin", from_coin.to_string().as_str()),
+            ("target_denom", target_denom.as_str()),
+            ("fee_amount", fee_amount.to_string().as_str()),
+        ]))
+}
+
+/**
+ * Swap the attached coin for an exact amount of the target denom through the direct pair.
+ * The input is worked out with the reverse simulation of the pair, the rest of the funds is refunded
+ */
+pub fn swap_denom_exact_out(deps: DepsMut, env: Env, info: MessageInfo, max_from_coin: Coin, target: Coin,
+                            to_address: Option<String>) -> Result<Response, ContractError> {
+    assert_not_paused(deps.as_ref())?;
+    let sender = info.sender.clone();
+    if !is_address_in_whitelist(deps.storage, sender.clone())? {
+        return Err(ContractError::Unauthorized {});
+    }
+    if max_from_coin.denom == target.denom {
+        return Err(ContractError::InvalidDenom {});
+    }
+    if max_from_coin.amount.is_zero() || target.amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+    assert_funds(&info.funds, &max_from_coin)?;
+
+    let asset_infos = [
+        AssetInfo::NativeToken { denom: max_from_coin.denom.clone() },
+        AssetInfo::NativeToken { denom: target.denom.clone() },
+    ];
+    let pair_key = pair_key(&asset_infos);
+    let pair_config = read_pair_config(deps.storage, &asset_infos)?;
+    if pair_config.is_disabled {
+        return Err(ContractError::PairConfigDisabled { pair: pair_name(&asset_infos) });
+    }
+    let to = match to_address {
+        Some(to_address) => deps.api.addr_validate(&to_address)?,
+        None => pair_config.to.clone().unwrap_or(sender.clone()),
+    };
+
+    // the pair needs the simulated offer amount, the router fee is charged on top of it
+    let simulation = query_reverse_simulation(&deps.querier, &pair_config, Asset {
+        info: asset_infos[1].clone(),
+        amount: target.amount,
+    })?;
+    let config = read_config(deps.storage)?;
+    let required = config.amount_with_fee(&pair_config, simulation.offer_amount);
+    if required > max_from_coin.amount {
+        return Err(ContractError::MaxInputExceeded { required, max: max_from_coin.amount });
+    }
+    let from_coin = Coin::new(required.u128(), max_from_coin.denom.clone());
+
+    let operations = vec![SwapOperation {
+        offer_asset_info: asset_infos[0].clone(),
+        ask_asset_info: asset_infos[1].clone(),
+    }];
+    apply_rate_limit(deps.storage, &env, &sender, &operations[0], required)?;
+
+    let fee_amount = config.fee_amount(&pair_config, required);
+    let offer_amount = required.checked_sub(fee_amount)?;
+    if offer_amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+    let refund = refund_coins(&info.funds, &from_coin);
+    let res = add_refund(Response::new(), &sender, refund.clone());
+    let res = charge_fee(deps.storage, res, &config, &pair_key, Asset {
+        info: asset_infos[0].clone(),
+        amount: fee_amount,
+    })?;
+
+    // the swap reverts unless the target amount is delivered
+    let route_res = start_swap_route(deps, env, sender, to, operations, offer_amount, Some(target.amount), None,
+                                     None, vec![], &refund)?;
+    Ok(res
+        .add_submessages(route_res.messages)
+        .add_attributes(route_res.attributes)
+        .add_attributes(vec![
+            ("max_from_coin", max_from_coin.to_string().as_str()),
+            ("from_coin", from_coin.to_string().as_str()),
+            ("target", target.to_string().as_str()),
+            ("fee_amount", fee_amount.to_string().as_str()),
+        ]))
+}
+
+/**
+ * Check that the funds hold the coin
+ */
+fn assert_funds(funds: &[Coin], coin: &Coin) -> Result<(), ContractError> {
+    let provided = funds
+        .iter()
+        .filter(|x| x.denom == coin.denom)
+        .fold(Uint128::zero(), |total, x| total + x.amount);
+    if provided < coin.amount {
+        return Err(ContractError::InsufficientFunds {
+            denom: coin.denom.clone(),
+            required: coin.amount,
+            provided,
+        });
+    }
+    Ok(())
+}
+
+/**
+ * The funds left once the swapped coin is taken, sorted by denom
+ */
+fn refund_coins(funds: &[Coin], swapped: &Coin) -> Vec<Coin> {
+    let provided = funds
+        .iter()
+        .filter(|x| x.denom == swapped.denom)
+        .fold(Uint128::zero(), |total, x| total + x.amount);
+    let mut refund: Vec<Coin> = funds
+        .iter()
+        .filter(|x| x.denom != swapped.denom && !x.amount.is_zero())
+        .cloned()
+        .collect();
+    if provided > swapped.amount {
+        refund.push(Coin { denom: swapped.denom.clone(), amount: provided - swapped.amount });
+    }
+    refund.sort_by(|a, b| a.denom.cmp(&b.denom));
+    refund
+}
+
+/**
+ * Send the refund back to the sender
+ */
+fn add_refund(res: Response, sender: &Addr, refund: Vec<Coin>) -> Response {
+    if refund.is_empty() {
+        return res;
+    }
+    let refund_attr = refund.iter().map(|coin| coin.to_string()).collect::<Vec<String>>().join(",");
+    res.add_message(BankMsg::Send {
+        to_address: sender.to_string(),
+        amount: refund,
+    })
+    .add_attribute("refund", refund_attr)
+}
+
+/**
+ * Send the router fee to the fee collector and record it against the pair
+ */
+fn charge_fee(storage: &mut dyn Storage, res: Response, config: &Config, pair_key: &[u8],
+              fee: Asset) -> Result<Response, ContractError> {
+    let fee_collector = match config.fee_collector.as_ref().filter(|_| !fee.amount.is_zero()) {
+        Some(fee_collector) => fee_collector,
+        None => return Ok(res),
+    };
+    let mut swap_info = read_swap_info_default_zero(storage, pair_key)?;
+    swap_info.total_fee_amount += fee.amount;
+    store_swap_infos(storage, pair_key, &swap_info)?;
+    Ok(res
+        .add_message(fee.into_msg(fee_collector.clone())?)
+        .add_attribute("fee_collector", fee_collector))
+}
+
+/**
+ * Deduct the router fee from the offer of a route, with the fee of its first pair.
+ * Returns the response charging the fee, the fee and the amount left to offer
+ */
+fn deduct_route_fee(storage: &mut dyn Storage, operation: &SwapOperation,
+                    amount: Uint128) -> Result<(Response, Uint128, Uint128), ContractError> {
+    let config = read_config(storage)?;
+    let asset_infos = operation.asset_infos();
+    let pair_config = read_pair_config(storage, &asset_infos)?;
+    let fee_amount = config.fee_amount(&pair_config, amount);
+    let offer_amount = amount.checked_sub(fee_amount)?;
+    if offer_amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
+    let res = charge_fee(storage, Response::new(), &config, &pair_key(&asset_infos), Asset {
+        info: operation.offer_asset_info.clone(),
+        amount: fee_amount,
+    })?;
+    Ok((res, fee_amount, offer_amount))
+}
+
+/**
+ * Swap the attached coin through a chain of registered pairs.
+ * Every hop sends its output back to the router, the reply of each hop feeds the next one.
+ */
+pub fn swap_route(deps: DepsMut, env: Env, info: MessageInfo, operations: Vec<SwapOperation>,
+                  minimum_receive: Option<Uint128>, to_address: Option<String>) -> Result<Response, ContractError> {
+    assert_not_paused(deps.as_ref())?;
+    let sender = info.sender.clone();
+    if !is_address_in_whitelist(deps.storage, sender.clone())? {
+        return Err(ContractError::Unauthorized {});
+    }
+    validate_swap_operations(deps.as_ref(), &operations)?;
+
+    let offer_asset_info = operations[0].offer_asset_info.clone();
     let payment = info
         .funds
         .iter()
-        .find(|x| x.denom == from_coin.denom && x.amount == from_coin.amount)
+        .find(|x| offer_asset_info.equal(&AssetInfo::NativeToken { denom: x.denom.clone() }))
         .ok_or_else(|| {
-            StdError::generic_err(format!("No {} assets are provided to swap.", from_coin.denom))
+            StdError::generic_err(format!("No {} assets are provided to swap.", offer_asset_info))
         })?;
+    if payment.amount.is_zero() {
+        return Err(ContractError::InvalidAmount {});
+    }
 
+    let to = match to_address {
+        Some(to_address) => deps.api.addr_validate(&to_address)?,
+        None => sender.clone(),
+    };
 
-    let asset_infos = [
-        AssetInfo::NativeToken {
-            denom: from_coin.denom.clone(),
-        },
-        AssetInfo::NativeToken {
-            denom: target_denom.clone(),
-        },
-    ];
-    let pair_key = pair_key(&asset_infos);
+    apply_rate_limit(deps.storage, &env, &sender, &operations[0], payment.amount)?;
+    let (res, fee_amount, offer_amount) = deduct_route_fee(deps.storage, &operations[0], payment.amount)?;
+    let route_res = start_swap_route(deps, env, sender, to, operations, offer_amount, minimum_receive, None, None,
+                                     vec![], &[])?;
+    Ok(res
+        .add_submessages(route_res.messages)
+        .add_attributes(route_res.attributes)
+        .add_attribute("fee_amount", fee_amount.to_string()))
+}
 
-    let mut swap_info = read_swap_info_default_zero(deps.storage, &pair_key)?;
+/**
+ * Check that the operations form a chain of enabled pairs within the configured max hops
+ */
+fn validate_swap_operations(deps: Deps, operations: &[SwapOperation]) -> Result<(), ContractError> {
+    let config = read_config(deps.storage)?;
+    if operations.is_empty() || operations.len() > config.max_hops as usize {
+        return Err(ContractError::InvalidSwapOperations {});
+    }
+    if operations.windows(2).any(|ops| !ops[0].ask_asset_info.equal(&ops[1].offer_asset_info)) {
+        return Err(ContractError::InvalidSwapOperations {});
+    }
+    for operation in operations.iter() {
+        if operation.offer_asset_info.equal(&operation.ask_asset_info) {
+            return Err(ContractError::InvalidDenom {});
+        }
+        operation.offer_asset_info.check(deps.api)?;
+        operation.ask_asset_info.check(deps.api)?;
+        let pair_config = read_pair_config(deps.storage, &operation.asset_infos())?;
+        if pair_config.is_disabled {
+            return Err(ContractError::PairConfigDisabled { pair: pair_name(&operation.asset_infos()) });
+        }
+    }
+    Ok(())
+}
+
+/**
+ * Handle the hook of a cw20 token sent to the router, the token is the offer asset
+ */
+pub fn receive_cw20(deps: DepsMut, env: Env, info: MessageInfo, cw20_msg: Cw20ReceiveMsg) -> Result<Response, ContractError> {
+    match from_binary(&cw20_msg.msg)? {
+        Cw20HookMsg::Swap { target_asset, to, minimum_receive } => {
+            assert_not_paused(deps.as_ref())?;
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            if !is_address_in_whitelist(deps.storage, sender.clone())? {
+                return Err(ContractError::Unauthorized {});
+            }
+            if cw20_msg.amount.is_zero() {
+                return Err(ContractError::InvalidAmount {});
+            }
 
+            let operations = vec![SwapOperation {
+                offer_asset_info: AssetInfo::Token { contract_addr: info.sender },
+                ask_asset_info: target_asset,
+            }];
+            validate_swap_operations(deps.as_ref(), &operations)?;
 
-    let pair_config = read_pair_config(deps.storage, &pair_key)?;
-    if pair_config.is_disabled {
-        return Err(ContractError::PairNotFound {});
+            let to = match to {
+                Some(to) => deps.api.addr_validate(&to)?,
+                None => sender.clone(),
+            };
+            apply_rate_limit(deps.storage, &env, &sender, &operations[0], cw20_msg.amount)?;
+            let (res, fee_amount, offer_amount) = deduct_route_fee(deps.storage, &operations[0], cw20_msg.amount)?;
+            let route_res = start_swap_route(deps, env, sender, to, operations, offer_amount, minimum_receive, None,
+                                             None, vec![], &[])?;
+            Ok(res
+                .add_submessages(route_res.messages)
+                .add_attributes(route_res.attributes)
+                .add_attribute("fee_amount", fee_amount.to_string()))
+        }
     }
+}
 
+/**
+ * Save the route state and dispatch its first hop.
+ * The refund is sent back before the first hop, its coins in the ask asset of the hop are not part of its output
+ */
+#[allow(clippy::too_many_arguments)]
+fn start_swap_route(deps: DepsMut, env: Env, sender: Addr, to: Addr, operations: Vec<SwapOperation>,
+                    offer_amount: Uint128, minimum_receive: Option<Uint128>, belief_price: Option<Decimal>,
+                    max_spread: Option<Decimal>, split_offers: Vec<(Addr, Uint128)>,
+                    refund: &[Coin]) -> Result<Response, ContractError> {
+    let offer_asset_info = operations[0].offer_asset_info.clone();
+    let refunded_ask_amount = refund
+        .iter()
+        .filter(|coin| operations[0].ask_asset_info.equal(&AssetInfo::NativeToken { denom: coin.denom.clone() }))
+        .fold(Uint128::zero(), |total, coin| total + coin.amount);
+    let mut state = SwapRouteState {
+        sender,
+        to: to.clone(),
+        operations,
+        hop_index: 0,
+        offer_amount,
+        ask_balance_before: Uint128::zero(),
+        minimum_receive,
+        belief_price,
+        max_spread,
+        split_offers,
+        pending_replies: 0,
+        hop_spread_amount: Uint128::zero(),
+        hop_commission_amount: Uint128::zero(),
+    };
+    let sub_msgs = execute_swap_operation(deps, &env, &mut state, refunded_ask_amount)?;
 
-    // swap
-    let asset = Asset {
-        amount: payment.amount,
-        info: asset_infos[0].clone(),
+    Ok(Response::new()
+        .add_submessages(sub_msgs)
+        .add_attributes(vec![
+            ("action", "swap_route"),
+            ("offer_asset", offer_asset_info.to_string().as_str()),
+            ("offer_amount", offer_amount.to_string().as_str()),
+            ("hops", state.operations.len().to_string().as_str()),
+            ("to", to.as_str()),
+        ]))
+}
+
+/**
+ * Dispatch the current hop of the route, one submessage per pool of a split hop.
+ * The pools send the output back to the router, `ask_outflow` leaves the router before them
+ */
+fn execute_swap_operation(deps: DepsMut, env: &Env, state: &mut SwapRouteState,
+                          ask_outflow: Uint128) -> Result<Vec<SubMsg>, ContractError> {
+    let operation = state.operations[state.hop_index as usize].clone();
+    let asset_infos = operation.asset_infos();
+    let pair_config = read_pair_config(deps.storage, &asset_infos)?;
+    let max_spread = clamp_max_spread(state.max_spread, pair_config.max_spread);
+    let pool_offers = hop_pool_offers(state, &pair_config);
+
+    state.ask_balance_before = operation
+        .ask_asset_info
+        .query_pool(&deps.querier, env.contract.address.clone())?
+        .checked_sub(ask_outflow)?;
+    state.pending_replies = pool_offers.len() as u32;
+    state.hop_spread_amount = Uint128::zero();
+    state.hop_commission_amount = Uint128::zero();
+    store_swap_route_state(deps.storage, state)?;
+
+    let config = read_config(deps.storage)?;
+    let mut sub_msgs = vec![];
+    for (pool_address, amount) in pool_offers {
+        let pool_config = pair_config.with_pool(pool_address);
+        let offer_asset = Asset {
+            info: operation.offer_asset_info.clone(),
+            amount,
+        };
+        assert_oracle_price(deps.as_ref(), &config, &pool_config, &offer_asset, &operation.ask_asset_info)?;
+        let msg = pair_swap_msg(&pool_config, offer_asset, state.belief_price, max_spread,
+                                env.contract.address.to_string())?;
+        sub_msgs.push(SubMsg::reply_on_success(msg, SWAP_ROUTE_REPLY_ID));
+    }
+    Ok(sub_msgs)
+}
+
+/**
+ * Check that the simulated price of the swap is within the max deviation of the pair from the oracle price.
+ * The prices are in ask assets per offer asset
+ */
+fn assert_oracle_price(deps: Deps, config: &Config, pair_config: &PairConfig, offer_asset: &Asset,
+                       ask_asset_info: &AssetInfo) -> Result<(), ContractError> {
+    let (oracle, max_deviation) = match (&config.oracle, pair_config.max_oracle_deviation) {
+        (Some(oracle), Some(max_deviation)) => (oracle, max_deviation),
+        _ => return Ok(()),
     };
-    let pair_address = pair_config.pair_address.clone();
-    let offer_asset = asset.clone();
-    let simulation_response = query_simulation(&deps.querier, pair_address.clone().to_string(), offer_asset.clone())?;
-
-    swap_info.total_amount_out += simulation_response.return_amount;
-    swap_info.total_amount_in += payment.amount;
-
-    let mut _max_spread = None;
-    if pair_config.max_spread.is_some() {
-        _max_spread = Some(pair_config.max_spread.unwrap());
-    }
-    let mut to_addr = to_address;
-    if to_addr.is_none() {
-        to_addr = Some(pair_config.to.unwrap_or(sender.clone()).to_string());
-    }
-    let swap = SwapMsg::Swap {
-        offer_asset: asset,
-        belief_price: None,
-        max_spread: _max_spread,
-        to: to_addr,
+    let offer_price = query_oracle_price(&deps.querier, oracle, &offer_asset.info)?;
+    let ask_price = query_oracle_price(&deps.querier, oracle, ask_asset_info)?;
+    if ask_price.is_zero() {
+        return Err(StdError::generic_err(format!("No oracle price for {}", ask_asset_info)).into());
+    }
+    let oracle_price = offer_price.checked_div(ask_price).map_err(|e| StdError::generic_err(e.to_string()))?;
+
+    let simulation = query_simulation(&deps.querier, pair_config, offer_asset.clone())?;
+    let price = Decimal256::from_ratio(simulation.return_amount, offer_asset.amount);
+    let deviation = if oracle_price.is_zero() {
+        Decimal256::MAX
+    } else {
+        price.abs_diff(oracle_price) / oracle_price
     };
+    if deviation > Decimal256::from(max_deviation) {
+        return Err(ContractError::OraclePriceDeviation {
+            pair: pair_config.pair_address.to_string(),
+            deviation,
+            max: max_deviation,
+        });
+    }
+    Ok(())
+}
 
-    let sub_msg = SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
-        contract_addr: pair_address.clone().to_string(),
-        msg: to_binary(&swap)?,
-        funds: vec![Coin {
-            denom: payment.denom.to_string(),
-            amount: payment.amount,
-        }],
-    }));
+/**
+ * The offer amount per pool of the current hop, only the first hop of a swap is split
+ */
+fn hop_pool_offers(state: &SwapRouteState, pair_config: &PairConfig) -> Vec<(Addr, Uint128)> {
+    if state.hop_index == 0 && !state.split_offers.is_empty() {
+        state.split_offers.clone()
+    } else {
+        vec![(pair_config.pair_address.clone(), state.offer_amount)]
+    }
+}
 
+/**
+ * Handle the reply of a route hop: account the received amount and either
+ * dispatch the next hop or deliver the final output to the recipient
+ */
+pub fn swap_route_reply(deps: DepsMut, env: Env, response: SubMsgResponse) -> Result<Response, ContractError> {
+    let mut state = read_swap_route_state(deps.storage)?;
+    let operation = state.operations[state.hop_index as usize].clone();
+    let asset_infos = operation.asset_infos();
+    let pair_key = pair_key(&asset_infos);
+    let pair_config = read_pair_config(deps.storage, &asset_infos)?;
 
-    let res = Response::new()
-        .add_submessage(sub_msg)
-        .add_attributes(vec![
-            ("action", "swap"),
-            ("from_coin", from_coin.to_string().as_str()),
-            ("target_denom", target_denom.as_str()),
-        ]);
+    // the replies of the pools arrive in the order of their submessages
+    let pool_offers = hop_pool_offers(&state, &pair_config);
+    let (pool_address, pool_offer_amount) = pool_offers[pool_offers.len() - state.pending_replies as usize].clone();
+    state.hop_spread_amount += pair_event_amount(&response, &pool_address, "spread_amount");
+    state.hop_commission_amount += pair_event_amount(&response, &pool_address, "commission_amount");
+    state.pending_replies -= 1;
+    if state.pending_replies > 0 {
+        store_swap_route_state(deps.storage, &state)?;
+        return Ok(Response::new().add_attributes(vec![
+            ("action", "swap_route_pool"),
+            ("pool_address", pool_address.as_str()),
+            ("offer_amount", pool_offer_amount.to_string().as_str()),
+        ]));
+    }
+
+    let ask_balance = operation
+        .ask_asset_info
+        .query_pool(&deps.querier, env.contract.address.clone())?;
+    let return_amount = ask_balance.checked_sub(state.ask_balance_before)?;
+
+    let is_last_hop = (state.hop_index as usize) + 1 == state.operations.len();
+    if is_last_hop {
+        if let Some(minimum_receive) = state.minimum_receive {
+            if return_amount < minimum_receive {
+                return Err(ContractError::AssertionMinimumReceive {
+                    receive: return_amount,
+                    minimum_receive,
+                });
+            }
+        }
+    }
+
+    let spread_amount = state.hop_spread_amount;
+    let commission_amount = state.hop_commission_amount;
 
+    let mut swap_info = read_swap_info_default_zero(deps.storage, &pair_key)?;
+    swap_info.total_amount_in += state.offer_amount;
+    swap_info.total_amount_out += return_amount;
+    swap_info.total_spread_amount += spread_amount;
+    swap_info.total_commission_amount += commission_amount;
     store_swap_infos(deps.storage, &pair_key, &swap_info)?;
+    add_swap_volume(deps.storage, &pair_key, env.block.time.seconds(), state.offer_amount, return_amount)?;
 
-    Ok(res)
-}
+    let mut caller_stats = read_caller_stats(deps.storage, &state.sender, &pair_key)?.unwrap_or(CallerStats {
+        asset_infos: pair_config.asset_infos.clone(),
+        swap_count: 0,
+        total_amount_in: Uint128::zero(),
+        total_amount_out: Uint128::zero(),
+        last_swap_height: 0,
+        last_swap_time: 0,
+    });
+    caller_stats.swap_count += 1;
+    caller_stats.total_amount_in += state.offer_amount;
+    caller_stats.total_amount_out += return_amount;
+    caller_stats.last_swap_height = env.block.height;
+    caller_stats.last_swap_time = env.block.time.seconds();
+    store_caller_stats(deps.storage, &state.sender, &pair_key, &caller_stats)?;
+    record_price_snapshot(deps.storage, &deps.querier, &env, &pair_key, &pair_config)?;
+
+    let res = Response::new().add_attributes(vec![
+        ("action", "swap_route_hop"),
+        ("hop_index", state.hop_index.to_string().as_str()),
+        ("offer_asset", operation.offer_asset_info.to_string().as_str()),
+        ("ask_asset", operation.ask_asset_info.to_string().as_str()),
+        ("offer_amount", state.offer_amount.to_string().as_str()),
+        ("return_amount", return_amount.to_string().as_str()),
+        ("spread_amount", spread_amount.to_string().as_str()),
+        ("commission_amount", commission_amount.to_string().as_str()),
+    ]);
 
+    if !is_last_hop {
+        state.hop_index += 1;
+        state.offer_amount = return_amount;
+        // the later hops are limited on their measured offer amount
+        let next_operation = state.operations[state.hop_index as usize].clone();
+        apply_rate_limit(deps.storage, &env, &state.sender, &next_operation, return_amount)?;
+        let sub_msgs = execute_swap_operation(deps, &env, &mut state, Uint128::zero())?;
+        return Ok(res.add_submessages(sub_msgs));
+    }
+
+    remove_swap_route_state(deps.storage);
 
+    let return_asset = Asset {
+        info: operation.ask_asset_info,
+        amount: return_amount,
+    };
+    Ok(res
+        .add_message(return_asset.into_msg(state.to.clone())?)
+        .add_attributes(vec![
+            ("return_amount", return_amount.to_string()),
+            ("to", state.to.to_string()),
+        ]))
+}
 
+/**
+ * Sum an amount attribute of the wasm events emitted by the pair, zero when the pair does not report it
+ */
+fn pair_event_amount(response: &SubMsgResponse, pair_address: &Addr, key: &str) -> Uint128 {
+    response
+        .events
+        .iter()
+        .filter(|event| event.ty == "wasm")
+        .filter(|event| {
+            event.attributes.iter().any(|attr| attr.key == "_contract_address" && attr.value == pair_address.as_str())
+        })
+        .flat_map(|event| event.attributes.iter())
+        .filter(|attr| attr.key == key)
+        .filter_map(|attr| Uint128::from_str(&attr.value).ok())
+        .fold(Uint128::zero(), |total, amount| total + amount)
+}
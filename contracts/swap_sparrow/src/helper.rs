@@ -1,6 +1,12 @@
+use crate::msg::{PairCw20HookMsg, StableCw20HookMsg, StableQueryMsg, StableSwapMsg, SwapMsg, SwapQueryMsg};
 use crate::querier::{query_balance, query_token_balance};
+use crate::state::{PairConfig, PairType};
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Api, QuerierWrapper, StdResult, Uint128};
+use cosmwasm_std::{
+    to_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal, QuerierWrapper, StdError, StdResult, Uint128,
+    WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
 use std::fmt;
 
 /// This enum describes available Token types.
@@ -117,6 +123,120 @@ pub struct Asset {
     pub amount: Uint128,
 }
 
+impl Asset {
+    /// Returns a message of type [`CosmosMsg`] that transfers the asset to the recipient.
+    /// ## Params
+    /// * **self** is the type of the caller object.
+    ///
+    /// * **recipient** is the address that receives the asset.
+    pub fn into_msg(self, recipient: Addr) -> StdResult<CosmosMsg> {
+        match self.info {
+            AssetInfo::Token { contract_addr } => Ok(CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: recipient.to_string(),
+                    amount: self.amount,
+                })?,
+                funds: vec![],
+            })),
+            AssetInfo::NativeToken { denom } => Ok(CosmosMsg::Bank(BankMsg::Send {
+                to_address: recipient.to_string(),
+                amount: vec![Coin {
+                    denom,
+                    amount: self.amount,
+                }],
+            })),
+        }
+    }
+}
+
+/// ## Description
+/// Returns the asset of the pair other than the offer asset.
+fn ask_asset_info(pair_config: &PairConfig, offer_asset_info: &AssetInfo) -> AssetInfo {
+    if pair_config.asset_infos[0].equal(offer_asset_info) {
+        pair_config.asset_infos[1].clone()
+    } else {
+        pair_config.asset_infos[0].clone()
+    }
+}
+
+fn unsupported_pair_type(pair_config: &PairConfig) -> StdError {
+    StdError::generic_err(format!("Unsupported pair type {}", pair_config.pair_type))
+}
+
+/// ## Description
+/// Returns the message swapping the offer asset through the pair, in the shape of the pair type.
+/// The cw20 tokens are sent to the pair with its swap hook, the native coins are attached to the swap.
+pub fn pair_swap_msg(pair_config: &PairConfig, offer_asset: Asset, belief_price: Option<Decimal>,
+                     max_spread: Option<Decimal>, to: String) -> StdResult<CosmosMsg> {
+    let pair_address = pair_config.pair_address.to_string();
+    let ask_asset_info = Some(ask_asset_info(pair_config, &offer_asset.info));
+    let msg = match (&offer_asset.info, pair_config.pair_type) {
+        (_, PairType::NativeDex) => return Err(unsupported_pair_type(pair_config)),
+        (AssetInfo::Token { contract_addr }, pair_type) => {
+            let hook = match pair_type {
+                PairType::AstroportStable => to_binary(&StableCw20HookMsg::Swap {
+                    ask_asset_info,
+                    belief_price,
+                    max_spread,
+                    to: Some(to),
+                })?,
+                _ => to_binary(&PairCw20HookMsg::Swap { belief_price, max_spread, to: Some(to) })?,
+            };
+            WasmMsg::Execute {
+                contract_addr: contract_addr.to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Send {
+                    contract: pair_address,
+                    amount: offer_asset.amount,
+                    msg: hook,
+                })?,
+                funds: vec![],
+            }
+        }
+        (AssetInfo::NativeToken { denom }, pair_type) => {
+            let funds = vec![Coin { denom: denom.to_string(), amount: offer_asset.amount }];
+            let msg = match pair_type {
+                PairType::AstroportStable => to_binary(&StableSwapMsg::Swap {
+                    offer_asset,
+                    ask_asset_info,
+                    belief_price,
+                    max_spread,
+                    to: Some(to),
+                })?,
+                _ => to_binary(&SwapMsg::Swap { offer_asset, belief_price, max_spread, to: Some(to) })?,
+            };
+            WasmMsg::Execute { contract_addr: pair_address, msg, funds }
+        }
+    };
+    Ok(CosmosMsg::Wasm(msg))
+}
+
+/// ## Description
+/// Returns the simulation query of the offer asset, in the shape of the pair type.
+pub fn pair_simulation_msg(pair_config: &PairConfig, offer_asset: Asset) -> StdResult<Binary> {
+    match pair_config.pair_type {
+        PairType::AstroportXyk | PairType::Terraswap => to_binary(&SwapQueryMsg::Simulation { offer_asset }),
+        PairType::AstroportStable => to_binary(&StableQueryMsg::Simulation {
+            ask_asset_info: Some(ask_asset_info(pair_config, &offer_asset.info)),
+            offer_asset,
+        }),
+        PairType::NativeDex => Err(unsupported_pair_type(pair_config)),
+    }
+}
+
+/// ## Description
+/// Returns the reverse simulation query of the ask asset, in the shape of the pair type.
+pub fn pair_reverse_simulation_msg(pair_config: &PairConfig, ask_asset: Asset) -> StdResult<Binary> {
+    match pair_config.pair_type {
+        PairType::AstroportXyk | PairType::Terraswap => to_binary(&SwapQueryMsg::ReverseSimulation { ask_asset }),
+        PairType::AstroportStable => to_binary(&StableQueryMsg::ReverseSimulation {
+            offer_asset_info: Some(ask_asset_info(pair_config, &ask_asset.info)),
+            ask_asset,
+        }),
+        PairType::NativeDex => Err(unsupported_pair_type(pair_config)),
+    }
+}
+
 /// ## Description
 /// Calculates a pair key from the specified parameters in the `asset_infos` variable.
 /// ## Params
@@ -127,3 +247,8 @@ pub fn pair_key(asset_infos: &[AssetInfo; 2]) -> Vec<u8> {
 
     [asset_infos[0].as_bytes(), asset_infos[1].as_bytes()].concat()
 }
+
+/// The name of a pair in the errors, its assets in the given order
+pub fn pair_name(asset_infos: &[AssetInfo; 2]) -> String {
+    format!("{}-{}", asset_infos[0], asset_infos[1])
+}
@@ -1,6 +1,10 @@
-use cosmwasm_std::{Addr, Coin, Decimal, Uint128};
+use cosmwasm_std::{Addr, Coin, Decimal, Decimal256, Uint128};
 use crate::helper::{Asset, AssetInfo};
+use crate::state::{Granularity, OwnershipProposal, PairType, RateLimit, Role};
 use cosmwasm_schema::{cw_serde, QueryResponses};
+use cw20::Cw20ReceiveMsg;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
 #[cw_serde]
 pub struct InstantiateMsg {
@@ -21,20 +25,119 @@ pub struct PairConfigMsg {
 #[cw_serde]
 pub struct ConfigResponse {
     pub owner: Addr,
+    pub max_hops: u32,
+    pub paused: bool,
+    pub paused_by: Option<Addr>,
+    pub fee_bps: u16,
+    pub fee_collector: Option<Addr>,
+    pub max_split_pools: u32,
+    pub min_split_chunk: Uint128,
+    pub oracle: Option<Addr>,
+    pub ownership_proposal: Option<OwnershipProposal>,
 }
 
 #[cw_serde]
 pub struct SwapInfoResponse {
     pub total_amount_in: Uint128,
     pub total_amount_out: Uint128,
+    pub total_spread_amount: Uint128,
+    pub total_commission_amount: Uint128,
+    pub total_fee_amount: Uint128,
+}
+
+#[cw_serde]
+pub struct CallerStatsResponse {
+    pub caller: Addr,
+    pub asset_infos: [AssetInfo; 2],
+    pub swap_count: u64,
+    pub total_amount_in: Uint128,
+    pub total_amount_out: Uint128,
+    pub last_swap_height: u64,
+    pub last_swap_time: u64,
+}
+
+#[cw_serde]
+pub struct CallerStatsListResponse {
+    pub stats: Vec<CallerStatsResponse>,
+}
+
+#[cw_serde]
+pub struct VolumeBucketResponse {
+    /// Start of the bucket, in seconds
+    pub bucket_start: u64,
+    pub swap_count: u64,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+}
+
+#[cw_serde]
+pub struct VolumeHistoryResponse {
+    pub granularity: Granularity,
+    pub buckets: Vec<VolumeBucketResponse>,
+}
+
+#[cw_serde]
+pub struct RateLimitResponse {
+    pub offer_asset: AssetInfo,
+    pub rate_limit: Option<RateLimit>,
 }
 
 #[cw_serde]
 pub struct PairConfigResponse {
+    pub asset_infos: [AssetInfo; 2],
     pub pair_address: Addr,
     pub is_disabled: bool,
     pub max_spread: Option<Decimal>,
     pub to: Option<Addr>,
+    pub fee_bps: Option<u16>,
+    pub pair_type: PairType,
+    pub pools: Vec<Addr>,
+    pub max_oracle_deviation: Option<Decimal>,
+}
+
+#[cw_serde]
+pub struct TwapResponse {
+    /// Assets of the pair in the order of its cumulative prices
+    pub asset_infos: [AssetInfo; 2],
+    /// Average price of the first asset in the second one, in the precision of the cumulative prices of the pair
+    pub price0_average: Decimal,
+    pub price1_average: Decimal,
+    /// Window actually covered, from the snapshot to the current block, in seconds
+    pub start_time: u64,
+    pub end_time: u64,
+}
+
+#[cw_serde]
+pub struct WhitelistEntry {
+    pub caller: Addr,
+    pub is_whitelist: bool,
+}
+
+#[cw_serde]
+pub struct WhitelistResponse {
+    pub entries: Vec<WhitelistEntry>,
+}
+
+#[cw_serde]
+pub struct RoleHoldersResponse {
+    pub role: Role,
+    pub holders: Vec<Addr>,
+}
+
+#[cw_serde]
+pub struct PairConfigsResponse {
+    pub pair_configs: Vec<PairConfigResponse>,
+}
+
+#[cw_serde]
+pub struct PairConfigWithSwapInfoResponse {
+    pub pair_config: PairConfigResponse,
+    pub swap_info: SwapInfoResponse,
+}
+
+#[cw_serde]
+pub struct AllPairConfigsResponse {
+    pub pair_configs: Vec<PairConfigWithSwapInfoResponse>,
 }
 
 #[cw_serde]
@@ -44,9 +147,23 @@ pub enum ExecuteMsg {
         pair_address: Addr,
         max_spread: Option<Decimal>,
         to: Option<Addr>,
+        /// Interface of the pair contract, default Astroport XYK
+        pair_type: Option<PairType>,
+    },
+    /// Proposes a new owner, who has to claim the ownership within `expires_in` seconds
+    ProposeNewOwner {
+        owner: String,
+        expires_in: u64,
     },
-    ChangeOwner {
-        new_owner: Addr,
+    DropOwnershipProposal {},
+    ClaimOwnership {},
+    GrantRole {
+        role: Role,
+        address: String,
+    },
+    RevokeRole {
+        role: Role,
+        address: String,
     },
     UpdatePairStatus {
         asset_infos: [AssetInfo; 2],
@@ -60,13 +177,112 @@ pub enum ExecuteMsg {
         caller: Addr,
         is_whitelist: bool,
     },
+    SetWhitelistBatch {
+        entries: Vec<(Addr, bool)>,
+    },
+    UpdateMaxHops {
+        max_hops: u32,
+    },
+    /// Sets the max number of pools of a split swap and the min amount offered to each of them
+    UpdateSplitConfig {
+        max_pools: u32,
+        min_chunk: Uint128,
+    },
+    /// Registers an additional pool of the pair, used by the split swaps
+    AddPairPool {
+        asset_infos: [AssetInfo; 2],
+        pool_address: String,
+    },
+    RemovePairPool {
+        asset_infos: [AssetInfo; 2],
+        pool_address: String,
+    },
+    /// Sets the oracle the prices of the pairs are checked against, `None` disables the check
+    UpdateOracle {
+        oracle: Option<String>,
+    },
+    /// Sets the max deviation of the simulated price of the pair from the oracle price, `None` disables the check
+    UpdatePairMaxOracleDeviation {
+        asset_infos: [AssetInfo; 2],
+        max_oracle_deviation: Option<Decimal>,
+    },
+    /// Sets the router fee charged on each swap and the address receiving it
+    UpdateFeeConfig {
+        fee_bps: u16,
+        fee_collector: Option<String>,
+    },
+    /// Overrides the router fee of a pair, `None` falls back to the fee of the config
+    UpdatePairFee {
+        asset_infos: [AssetInfo; 2],
+        fee_bps: Option<u16>,
+    },
+    /// Sets the limits on the swaps offering the asset, `None` removes them
+    SetRateLimit {
+        offer_asset: AssetInfo,
+        rate_limit: Option<RateLimit>,
+    },
+    /// Records the cumulative prices of every enabled pair, callable by anyone
+    SnapshotPrices {},
+    /// Rejects every swap until unpaused
+    Pause {},
+    Unpause {},
     SwapDenom {
         from_coin: Coin,
         target_denom: String,
         to_address: Option<String>,
+        /// Swap through the best route over the registered pairs instead of the direct pair
+        auto_route: Option<bool>,
+        /// Price expected from the pair, not supported with `auto_route`
+        belief_price: Option<Decimal>,
+        /// Max spread of the swap, clamped by the max spread configured for the pair
+        max_spread: Option<Decimal>,
+        /// Reverts the swap if the router receives less than this amount from the pair
+        minimum_receive: Option<Uint128>,
+        /// Divide the swap across the pools of the pair to maximize the return, not supported with `auto_route`
+        split: Option<bool>,
+    },
+    /// Swaps the least input for exactly `target` through the direct pair, the rest of the funds is refunded
+    SwapDenomExactOut {
+        /// Max coin swapped, the attached funds must hold it
+        max_from_coin: Coin,
+        target: Coin,
+        to_address: Option<String>,
+    },
+    /// Swaps the attached funds through every operation in order, feeding the output of each hop into the next
+    SwapRoute {
+        operations: Vec<SwapOperation>,
+        minimum_receive: Option<Uint128>,
+        to_address: Option<String>,
+    },
+    /// Receives a cw20 token with a [`Cw20HookMsg`]
+    Receive(Cw20ReceiveMsg),
+}
+
+/// This enum describes the hook messages of the cw20 tokens sent to the router.
+#[cw_serde]
+pub enum Cw20HookMsg {
+    /// Swaps the received token for the target asset through its registered pair
+    Swap {
+        target_asset: AssetInfo,
+        to: Option<String>,
+        minimum_receive: Option<Uint128>,
     },
 }
 
+/// This structure describes a single hop of a swap route, executed against the registered pair of both assets.
+#[cw_serde]
+pub struct SwapOperation {
+    pub offer_asset_info: AssetInfo,
+    pub ask_asset_info: AssetInfo,
+}
+
+impl SwapOperation {
+    /// The assets of the pair executing the operation
+    pub fn asset_infos(&self) -> [AssetInfo; 2] {
+        [self.offer_asset_info.clone(), self.ask_asset_info.clone()]
+    }
+}
+
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum QueryMsg {
@@ -76,6 +292,11 @@ pub enum QueryMsg {
     QueryIsSwapWhitelist {
         caller: Addr
     },
+    #[returns(WhitelistResponse)]
+    QueryWhitelist {
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
     #[returns(PairConfigResponse)]
     QueryPairConfig {
         asset_infos: [AssetInfo; 2]
@@ -89,6 +310,18 @@ pub enum QueryMsg {
         asset_infos: [AssetInfo; 2],
         offer_asset: Asset,
     },
+    /// Simulates every request like [`QueryMsg::QuerySimulation`], the errors are reported per request
+    #[returns(SimulationBatchResponse)]
+    QuerySimulationBatch {
+        requests: Vec<([AssetInfo; 2], Asset)>,
+    },
+    /// Quotes a swap with the router fee and the price impact, through the direct pair of both assets when
+    /// registered, through the best route otherwise
+    #[returns(QuoteResponse)]
+    QueryQuote {
+        offer_asset: Asset,
+        ask_asset_info: AssetInfo,
+    },
     #[returns(ReverseSimulationResponse)]
     QueryReverseSimulation {
         asset_infos: [AssetInfo; 2],
@@ -98,6 +331,59 @@ pub enum QueryMsg {
     QueryCumulativePrices {
         asset_infos: [AssetInfo; 2],
     },
+    /// Time-weighted average prices of a pair over at least the last `window_seconds`
+    #[returns(TwapResponse)]
+    QueryTwap {
+        asset_infos: [AssetInfo; 2],
+        window_seconds: u64,
+    },
+    #[returns(RoleHoldersResponse)]
+    QueryRoleHolders {
+        role: Role,
+        start_after: Option<Addr>,
+        limit: Option<u32>,
+    },
+    #[returns(BestRouteResponse)]
+    QueryBestRoute {
+        offer_asset: Asset,
+        ask_asset_info: AssetInfo,
+    },
+    #[returns(PairConfigsResponse)]
+    QueryPairsByAsset {
+        asset_info: AssetInfo,
+        start_after: Option<[AssetInfo; 2]>,
+        limit: Option<u32>,
+    },
+    #[returns(AllPairConfigsResponse)]
+    QueryAllPairConfigs {
+        start_after: Option<[AssetInfo; 2]>,
+        limit: Option<u32>,
+    },
+    /// Hourly or daily swap volume of a pair, for the buckets starting within `[from, to)`
+    #[returns(VolumeHistoryResponse)]
+    QueryVolumeHistory {
+        asset_infos: [AssetInfo; 2],
+        from: u64,
+        to: u64,
+        granularity: Granularity,
+    },
+    #[returns(RateLimitResponse)]
+    QueryRateLimit {
+        offer_asset: AssetInfo,
+    },
+    /// Swaps of a caller through a pair, zero when the caller never swapped through it
+    #[returns(CallerStatsResponse)]
+    QueryCallerStats {
+        caller: Addr,
+        asset_infos: [AssetInfo; 2],
+    },
+    /// Swaps of a caller through every pair it swapped through
+    #[returns(CallerStatsListResponse)]
+    QueryCallerStatsList {
+        caller: Addr,
+        start_after: Option<[AssetInfo; 2]>,
+        limit: Option<u32>,
+    },
 }
 
 #[cw_serde]
@@ -110,6 +396,52 @@ pub enum SwapMsg {
     },
 }
 
+/// This enum describes the hook message of the pair contract to swap a cw20 token.
+#[cw_serde]
+pub enum PairCw20HookMsg {
+    Swap {
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    },
+}
+
+/// This enum describes the swap message of the Astroport stable pairs, which need the ask asset of the pool.
+#[cw_serde]
+pub enum StableSwapMsg {
+    Swap {
+        offer_asset: Asset,
+        ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    },
+}
+
+/// This enum describes the hook message of the Astroport stable pairs to swap a cw20 token.
+#[cw_serde]
+pub enum StableCw20HookMsg {
+    Swap {
+        ask_asset_info: Option<AssetInfo>,
+        belief_price: Option<Decimal>,
+        max_spread: Option<Decimal>,
+        to: Option<String>,
+    },
+}
+
+/// This enum describes the simulation queries of the Astroport stable pairs.
+#[cw_serde]
+pub enum StableQueryMsg {
+    Simulation {
+        offer_asset: Asset,
+        ask_asset_info: Option<AssetInfo>,
+    },
+    ReverseSimulation {
+        offer_asset_info: Option<AssetInfo>,
+        ask_asset: Asset,
+    },
+}
+
 #[cw_serde]
 #[derive(QueryResponses)]
 pub enum SwapQueryMsg {
@@ -122,6 +454,32 @@ pub enum SwapQueryMsg {
     /// Returns information about the cumulative prices in a [`CumulativePricesResponse`] object
     #[returns(CumulativePricesResponse)]
     CumulativePrices {},
+    /// Returns the assets traded by the pair in a [`PairInfo`] object
+    #[returns(PairInfo)]
+    Pair {},
+}
+
+/// The part of the pair info returned by the pair contracts that the router uses.
+/// The unknown fields of the pair implementations are ignored.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PairInfo {
+    pub asset_infos: Vec<AssetInfo>,
+    pub contract_addr: Addr,
+}
+
+/// This enum describes the price query of the oracle contracts.
+#[cw_serde]
+pub enum OracleQueryMsg {
+    QueryPrice { asset: String },
+}
+
+/// The part of the price returned by the oracle contracts that the router uses.
+/// The unknown fields of the oracle implementations are ignored.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OraclePriceResponse {
+    pub asset: String,
+    /// Price of the asset in the quote currency of the oracle
+    pub price: Decimal256,
 }
 
 /// This structure holds the parameters that are returned from a swap simulation response
@@ -135,6 +493,53 @@ pub struct SimulationResponse {
     pub commission_amount: Uint128,
 }
 
+/// This structure holds the result of a request of a batched simulation, either the simulation or the error.
+#[cw_serde]
+pub struct SimulationBatchResult {
+    pub simulation: Option<SimulationResponse>,
+    pub error: Option<String>,
+}
+
+/// This structure holds the results of a batched simulation, in the order of the requests.
+#[cw_serde]
+pub struct SimulationBatchResponse {
+    pub results: Vec<SimulationBatchResult>,
+}
+
+/// This structure holds the quote of a swap through the direct pair or the best route, as the router would execute it.
+#[cw_serde]
+pub struct QuoteResponse {
+    /// The pair of the first hop, the direct pair when registered
+    pub pair_address: Addr,
+    /// Whether a pair of the route is disabled, only the direct pair may be
+    pub is_disabled: bool,
+    /// The tightest max spread of the pairs of the route, each hop is checked against the max spread of its pair
+    pub max_spread: Option<Decimal>,
+    /// The hops of the quoted route, the direct pair is a single hop
+    pub operations: Vec<SwapOperation>,
+    /// The router fee deducted from the offer amount
+    pub fee_amount: Uint128,
+    /// The amount of ask assets returned by the last hop
+    pub return_amount: Uint128,
+    /// The simulation of each hop, in the order of the operations
+    pub hops: Vec<HopQuote>,
+    /// The ask assets returned per offer asset, router fee included
+    pub effective_price: Decimal,
+    /// The relative shortfall of the price of the route from its price for a small reference offer
+    pub price_impact: Decimal,
+}
+
+/// This structure holds the simulation of a hop of a quote, its amounts are in the ask asset of the hop.
+#[cw_serde]
+pub struct HopQuote {
+    pub ask_asset_info: AssetInfo,
+    /// The amount returned by the hop, offered to the next one
+    pub return_amount: Uint128,
+    /// The spread used in the swap operation
+    pub spread_amount: Uint128,
+    /// The amount of fees charged by the pair
+    pub commission_amount: Uint128,
+}
 
 /// This structure holds the parameters that are returned from a reverse swap simulation response.
 #[cw_serde]
@@ -160,5 +565,19 @@ pub struct CumulativePricesResponse {
     pub price1_cumulative_last: Uint128,
 }
 
+/// This structure holds the route with the best simulated output between two assets.
+#[cw_serde]
+pub struct BestRouteResponse {
+    /// The hops of the route
+    pub operations: Vec<SwapOperation>,
+    /// The simulated amount of ask assets returned by the route
+    pub return_amount: Uint128,
+}
+
 #[cw_serde]
-pub struct MigrateMsg {}
\ No newline at end of file
+pub struct MigrateMsg {
+    /// Assets of the pairs registered before the assets were stored with the pair configs,
+    /// their configs are rewritten and indexed by asset
+    #[serde(default)]
+    pub pairs: Vec<[AssetInfo; 2]>,
+}
\ No newline at end of file
@@ -1,8 +1,24 @@
-use cosmwasm_std::{Addr, BalanceResponse, BankQuery, Deps, QuerierWrapper, QueryRequest, StdResult, to_binary, Uint128, WasmQuery};
+use cosmwasm_std::{Addr, BalanceResponse, BankQuery, Decimal, Decimal256, Deps, Env, Order, QuerierWrapper, QueryRequest, StdError, StdResult, to_binary, Uint128, WasmQuery};
 use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg};
-use crate::helper::{Asset, AssetInfo, pair_key};
-use crate::msg::{ConfigResponse, CumulativePricesResponse, PairConfigResponse, ReverseSimulationResponse, SimulationResponse, SwapInfoResponse, SwapQueryMsg};
-use crate::state::{Config, PairConfig, read_config, read_pair_config, read_swap_info_default_zero, read_swap_whitelist, SwapInfo};
+use cw_storage_plus::Bound;
+use crate::error::ContractError;
+use crate::helper::{Asset, AssetInfo, pair_key, pair_reverse_simulation_msg, pair_simulation_msg};
+use crate::msg::{OracleQueryMsg, OraclePriceResponse, PairInfo, RateLimitResponse, VolumeBucketResponse, VolumeHistoryResponse, AllPairConfigsResponse, BestRouteResponse, CallerStatsListResponse, CallerStatsResponse, ConfigResponse, CumulativePricesResponse, HopQuote, PairConfigResponse, PairConfigsResponse, PairConfigWithSwapInfoResponse, ReverseSimulationResponse, QuoteResponse, SimulationBatchResponse, SimulationBatchResult, SimulationResponse, SwapInfoResponse, TwapResponse, SwapOperation, SwapQueryMsg, RoleHoldersResponse, WhitelistEntry, WhitelistResponse};
+use crate::state::{MAX_HOPS, MAX_TWAP_WINDOW, read_price_snapshot_before, CallerStats, Config, Granularity, read_rate_limit, read_volume_buckets, pair_configs, read_caller_stats, read_caller_stats_list, PairConfig, read_config, read_ownership_proposal, read_pair_config, read_swap_info_default_zero, read_role_holders, read_swap_whitelist, read_swap_whitelists, Role, SwapInfo};
+
+// settings for pagination
+const MAX_LIMIT: u32 = 30;
+const DEFAULT_LIMIT: u32 = 10;
+/// Max buckets of a volume history, a week of hourly buckets
+const MAX_VOLUME_BUCKETS: usize = 168;
+/// Max requests of a batched simulation
+const MAX_SIMULATION_BATCH: usize = 30;
+/// The reference offer of a quote is this fraction of the offer
+const QUOTE_REFERENCE_DIVISOR: u128 = 1_000;
+/// Max number of chunks a split swap is divided into
+const MAX_SPLIT_CHUNKS: u128 = 10;
+/// Max number of candidate routes simulated by the route search
+const MAX_ROUTE_CANDIDATES: usize = 10;
 
 /**
  * Query the config of the oracle
@@ -11,16 +27,32 @@ pub fn query_config(deps: Deps) -> StdResult<ConfigResponse> {
     let config: Config = read_config(deps.storage)?;
     Ok(ConfigResponse {
         owner: config.owner,
+        max_hops: config.max_hops,
+        paused: config.paused,
+        paused_by: config.paused_by,
+        fee_bps: config.fee_bps,
+        fee_collector: config.fee_collector,
+        max_split_pools: config.max_split_pools,
+        min_split_chunk: config.min_split_chunk,
+        oracle: config.oracle,
+        ownership_proposal: read_ownership_proposal(deps.storage)?,
     })
 }
 
 pub fn query_swap_info(deps: Deps, asset_infos: [AssetInfo; 2]) -> StdResult<SwapInfoResponse> {
     let pair_key = pair_key(&asset_infos);
     let swap_info: SwapInfo = read_swap_info_default_zero(deps.storage, &pair_key)?;
-    Ok(SwapInfoResponse {
+    Ok(swap_info_response(swap_info))
+}
+
+fn swap_info_response(swap_info: SwapInfo) -> SwapInfoResponse {
+    SwapInfoResponse {
         total_amount_in: swap_info.total_amount_in,
         total_amount_out: swap_info.total_amount_out,
-    })
+        total_spread_amount: swap_info.total_spread_amount,
+        total_commission_amount: swap_info.total_commission_amount,
+        total_fee_amount: swap_info.total_fee_amount,
+    }
 }
 
 pub fn query_is_swap_whitelist(deps: Deps, caller: Addr) -> StdResult<bool> {
@@ -28,20 +60,167 @@ pub fn query_is_swap_whitelist(deps: Deps, caller: Addr) -> StdResult<bool> {
     Ok(is_whitelist)
 }
 
+/**
+ * Query the holders of a role ordered by address
+ */
+pub fn query_role_holders(deps: Deps, role: Role, start_after: Option<Addr>, limit: Option<u32>) -> StdResult<RoleHoldersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let holders = read_role_holders(deps.storage, role, start_after, limit)?;
+    Ok(RoleHoldersResponse { role, holders })
+}
+
+/**
+ * Query the whitelist entries ordered by caller address
+ */
+pub fn query_whitelist(deps: Deps, start_after: Option<Addr>, limit: Option<u32>) -> StdResult<WhitelistResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let entries = read_swap_whitelists(deps.storage, start_after, limit)?
+        .into_iter()
+        .map(|(caller, is_whitelist)| WhitelistEntry { caller, is_whitelist })
+        .collect();
+    Ok(WhitelistResponse { entries })
+}
+
 
 /**
  * Query the pair config of the asset
  */
 pub fn query_pair_config(deps: Deps, asset_infos: [AssetInfo; 2]) -> StdResult<PairConfigResponse> {
-    let pair_key = pair_key(&asset_infos);
-    let pair_config: PairConfig = read_pair_config(deps.storage, &pair_key)?;
+    let pair_config = read_pair_config(deps.storage, &asset_infos)?;
+
+    Ok(pair_config_response(pair_config))
+}
+
+/**
+ * Query the pair configs that contain the asset, ordered by pair key
+ */
+pub fn query_pairs_by_asset(deps: Deps, asset_info: AssetInfo, start_after: Option<[AssetInfo; 2]>,
+                            limit: Option<u32>) -> StdResult<PairConfigsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(|asset_infos| pair_key(&asset_infos));
+    let asset_key = asset_info.as_bytes().to_vec();
+
+    // the asset is either the first or the second asset of a pair, merge both indexes
+    let pair_configs = pair_configs();
+    let mut pairs: Vec<(Vec<u8>, PairConfig)> = vec![];
+    for index in [&pair_configs.idx.asset_0, &pair_configs.idx.asset_1] {
+        let min = start_after.as_deref().map(Bound::exclusive);
+        for item in index
+            .prefix(asset_key.clone())
+            .range(deps.storage, min, None, Order::Ascending)
+            .take(limit)
+        {
+            pairs.push(item?);
+        }
+    }
+    pairs.sort_by(|a, b| a.0.cmp(&b.0));
+    pairs.truncate(limit);
+
+    Ok(PairConfigsResponse {
+        pair_configs: pairs
+            .into_iter()
+            .map(|(_, pair_config)| pair_config_response(pair_config))
+            .collect(),
+    })
+}
+
+/**
+ * Query every registered pair config with its accumulated swap info, ordered by pair key
+ */
+pub fn query_all_pair_configs(deps: Deps, start_after: Option<[AssetInfo; 2]>,
+                              limit: Option<u32>) -> StdResult<AllPairConfigsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(|asset_infos| pair_key(&asset_infos));
+    let min = start_after.as_deref().map(Bound::exclusive);
+
+    let pair_configs = pair_configs()
+        .range(deps.storage, min, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (pair_key, pair_config) = item?;
+            let swap_info = read_swap_info_default_zero(deps.storage, &pair_key)?;
+            Ok(PairConfigWithSwapInfoResponse {
+                pair_config: pair_config_response(pair_config),
+                swap_info: swap_info_response(swap_info),
+            })
+        })
+        .collect::<StdResult<Vec<PairConfigWithSwapInfoResponse>>>()?;
 
-    Ok(PairConfigResponse {
+    Ok(AllPairConfigsResponse { pair_configs })
+}
+
+pub fn query_caller_stats(deps: Deps, caller: Addr, asset_infos: [AssetInfo; 2]) -> StdResult<CallerStatsResponse> {
+    let stats = read_caller_stats(deps.storage, &caller, &pair_key(&asset_infos))?.unwrap_or(CallerStats {
+        asset_infos,
+        swap_count: 0,
+        total_amount_in: Uint128::zero(),
+        total_amount_out: Uint128::zero(),
+        last_swap_height: 0,
+        last_swap_time: 0,
+    });
+    Ok(caller_stats_response(caller, stats))
+}
+
+/// ## Description
+/// Returns the non-empty volume buckets of a pair starting within `[from, to)` in a [`VolumeHistoryResponse`] object,
+/// at most [`MAX_VOLUME_BUCKETS`], ordered by time.
+pub fn query_volume_history(deps: Deps, asset_infos: [AssetInfo; 2], from: u64, to: u64,
+                            granularity: Granularity) -> StdResult<VolumeHistoryResponse> {
+    let buckets = read_volume_buckets(deps.storage, &pair_key(&asset_infos), granularity, from, to, MAX_VOLUME_BUCKETS)?
+        .into_iter()
+        .map(|(bucket_start, bucket)| VolumeBucketResponse {
+            bucket_start,
+            swap_count: bucket.swap_count,
+            amount_in: bucket.amount_in,
+            amount_out: bucket.amount_out,
+        })
+        .collect();
+    Ok(VolumeHistoryResponse { granularity, buckets })
+}
+
+pub fn query_rate_limit(deps: Deps, offer_asset: AssetInfo) -> StdResult<RateLimitResponse> {
+    let rate_limit = read_rate_limit(deps.storage, &offer_asset)?;
+    Ok(RateLimitResponse { offer_asset, rate_limit })
+}
+
+/// ## Description
+/// Returns the stats of a caller for every pair it swapped through in a [`CallerStatsListResponse`] object,
+/// ordered by pair key.
+pub fn query_caller_stats_list(deps: Deps, caller: Addr, start_after: Option<[AssetInfo; 2]>,
+                               limit: Option<u32>) -> StdResult<CallerStatsListResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start_after = start_after.map(|asset_infos| pair_key(&asset_infos));
+    let stats = read_caller_stats_list(deps.storage, &caller, start_after, limit)?
+        .into_iter()
+        .map(|stats| caller_stats_response(caller.clone(), stats))
+        .collect();
+    Ok(CallerStatsListResponse { stats })
+}
+
+fn caller_stats_response(caller: Addr, stats: CallerStats) -> CallerStatsResponse {
+    CallerStatsResponse {
+        caller,
+        asset_infos: stats.asset_infos,
+        swap_count: stats.swap_count,
+        total_amount_in: stats.total_amount_in,
+        total_amount_out: stats.total_amount_out,
+        last_swap_height: stats.last_swap_height,
+        last_swap_time: stats.last_swap_time,
+    }
+}
+
+fn pair_config_response(pair_config: PairConfig) -> PairConfigResponse {
+    PairConfigResponse {
+        asset_infos: pair_config.asset_infos,
         pair_address: pair_config.pair_address,
         is_disabled: pair_config.is_disabled,
         max_spread: pair_config.max_spread,
         to: pair_config.to,
-    })
+        fee_bps: pair_config.fee_bps,
+        pair_type: pair_config.pair_type,
+        pools: pair_config.pools,
+        max_oracle_deviation: pair_config.max_oracle_deviation,
+    }
 }
 
 
@@ -98,31 +277,187 @@ pub fn query_balance(
 /// * **deps** is an object of type [`Deps`].
 ///
 /// * **offer_asset** is an object of type [`Asset`]. This is the asset to swap as well as an amount of the said asset.
-pub fn query_simulation(querier: &QuerierWrapper, contract_addr: String, offer_asset: Asset) -> StdResult<SimulationResponse> {
+pub fn query_simulation(querier: &QuerierWrapper, pair_config: &PairConfig, offer_asset: Asset) -> StdResult<SimulationResponse> {
     let simulation_response = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: contract_addr.clone(),
-        msg: to_binary(&SwapQueryMsg::Simulation { offer_asset })?,
+        contract_addr: pair_config.pair_address.to_string(),
+        msg: pair_simulation_msg(pair_config, offer_asset)?,
     }))?;
     Ok(simulation_response)
 }
 
+/// ## Description
+/// Returns the price of an asset quoted by the oracle, in the quote currency of the oracle.
+pub fn query_oracle_price(querier: &QuerierWrapper, oracle: &Addr, asset_info: &AssetInfo) -> StdResult<Decimal256> {
+    let res: OraclePriceResponse = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr: oracle.to_string(),
+        msg: to_binary(&OracleQueryMsg::QueryPrice { asset: asset_info.to_string() })?,
+    }))?;
+    Ok(res.price)
+}
+
+/// ## Description
+/// Returns the assets traded by a pair contract in a [`PairInfo`] object.
+pub fn query_pair_info(querier: &QuerierWrapper, contract_addr: String) -> StdResult<PairInfo> {
+    querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
+        contract_addr,
+        msg: to_binary(&SwapQueryMsg::Pair {})?,
+    }))
+}
+
 /// ## Description
 /// Returns information about a reverse swap simulation in a [`ReverseSimulationResponse`] object.
 /// ## Params
 /// * **deps** is an object of type [`Deps`].
 ///
 /// * **ask_asset** is an object of type [`Asset`]. This is the asset to swap to as well as the desired
-/// amount of ask assets to receive from the swap.
+///   amount of ask assets to receive from the swap.
 pub fn query_reverse_simulation(
-    querier: &QuerierWrapper, contract_addr: String, ask_asset: Asset,
+    querier: &QuerierWrapper, pair_config: &PairConfig, ask_asset: Asset,
 ) -> StdResult<ReverseSimulationResponse> {
     let reverse_simulation_response = querier.query(&QueryRequest::Wasm(WasmQuery::Smart {
-        contract_addr: contract_addr.clone(),
-        msg: to_binary(&SwapQueryMsg::ReverseSimulation { ask_asset })?,
+        contract_addr: pair_config.pair_address.to_string(),
+        msg: pair_reverse_simulation_msg(pair_config, ask_asset)?,
     }))?;
     Ok(reverse_simulation_response)
 }
 
+/// ## Description
+/// Returns the simulation of an offer through a registered pair, once the router fee is deducted from the offer.
+pub fn query_pair_simulation(deps: Deps, asset_infos: [AssetInfo; 2], offer_asset: Asset) -> StdResult<SimulationResponse> {
+    let pair_config = read_pair_config(deps.storage, &asset_infos)?;
+    let mut offer_asset = offer_asset;
+    offer_asset.amount -= read_config(deps.storage)?.fee_amount(&pair_config, offer_asset.amount);
+    query_simulation(&deps.querier, &pair_config, offer_asset)
+}
+
+/// ## Description
+/// Returns the quote of a swap in a [`QuoteResponse`] object, through the direct pair of both assets or the best route.
+/// The price impact compares the price of the route for the offer with its price for a thousandth of the offer.
+pub fn query_quote(deps: Deps, offer_asset: Asset, ask_asset_info: AssetInfo) -> StdResult<QuoteResponse> {
+    if offer_asset.amount.is_zero() {
+        return Err(StdError::generic_err("Offer amount must be positive"));
+    }
+    // the direct pair is quoted when registered as SwapDenom swaps through it, the best route otherwise
+    let asset_infos = [offer_asset.info.clone(), ask_asset_info.clone()];
+    let operations = if pair_configs().has(deps.storage, &pair_key(&asset_infos)) {
+        vec![SwapOperation { offer_asset_info: offer_asset.info.clone(), ask_asset_info }]
+    } else {
+        query_best_route(deps, offer_asset.clone(), ask_asset_info)?.operations
+    };
+    let route_pair_configs = operations
+        .iter()
+        .map(|operation| read_pair_config(deps.storage, &operation.asset_infos()))
+        .collect::<Result<Vec<PairConfig>, ContractError>>()?;
+    let pair_config = &route_pair_configs[0];
+
+    // the router fee is charged with the fee of the first pair
+    let fee_amount = read_config(deps.storage)?.fee_amount(pair_config, offer_asset.amount);
+    let offer_amount = offer_asset.amount - fee_amount;
+    let simulations = simulate_route(deps, offer_amount, &operations)?;
+    let return_amount = simulations.last().map_or(offer_amount, |simulation| simulation.return_amount);
+
+    let reference_amount = offer_amount.multiply_ratio(1u128, QUOTE_REFERENCE_DIVISOR).max(Uint128::one());
+    let reference_return_amount = simulate_route(deps, reference_amount, &operations)?
+        .last()
+        .map_or(reference_amount, |simulation| simulation.return_amount);
+    let price = |return_amount: Uint128, offer_amount: Uint128| {
+        Decimal::checked_from_ratio(return_amount, offer_amount).map_err(|e| StdError::generic_err(e.to_string()))
+    };
+    let reference_price = price(reference_return_amount, reference_amount)?;
+    let price_impact = if reference_price.is_zero() || offer_amount.is_zero() {
+        Decimal::zero()
+    } else {
+        let route_price = price(return_amount, offer_amount)?;
+        (reference_price - route_price.min(reference_price)) / reference_price
+    };
+    let hops = operations
+        .iter()
+        .zip(simulations)
+        .map(|(operation, simulation)| HopQuote {
+            ask_asset_info: operation.ask_asset_info.clone(),
+            return_amount: simulation.return_amount,
+            spread_amount: simulation.spread_amount,
+            commission_amount: simulation.commission_amount,
+        })
+        .collect();
+
+    Ok(QuoteResponse {
+        pair_address: pair_config.pair_address.clone(),
+        is_disabled: route_pair_configs.iter().any(|pair_config| pair_config.is_disabled),
+        max_spread: route_pair_configs.iter().filter_map(|pair_config| pair_config.max_spread).min(),
+        operations,
+        fee_amount,
+        return_amount,
+        hops,
+        effective_price: price(return_amount, offer_asset.amount)?,
+        price_impact,
+    })
+}
+
+/// ## Description
+/// Returns the simulations of several offers in a [`SimulationBatchResponse`] object,
+/// a failing request reports its error without failing the others.
+pub fn query_simulation_batch(deps: Deps, requests: Vec<([AssetInfo; 2], Asset)>) -> StdResult<SimulationBatchResponse> {
+    if requests.len() > MAX_SIMULATION_BATCH {
+        return Err(StdError::generic_err(format!("At most {} simulations per batch", MAX_SIMULATION_BATCH)));
+    }
+    let results = requests
+        .into_iter()
+        .map(|(asset_infos, offer_asset)| match query_pair_simulation(deps, asset_infos, offer_asset) {
+            Ok(simulation) => SimulationBatchResult { simulation: Some(simulation), error: None },
+            Err(e) => SimulationBatchResult { simulation: None, error: Some(e.to_string()) },
+        })
+        .collect();
+    Ok(SimulationBatchResponse { results })
+}
+
+/// ## Description
+/// Divides an offer across the pools of a pair to maximize the total return.
+/// The offer is allocated chunk by chunk, each chunk to the pool whose simulation returns the most for it,
+/// a pool failing its simulation gets no share. Returns the offer amount per pool, without the empty ones.
+pub fn query_split_offers(
+    querier: &QuerierWrapper, pair_config: &PairConfig, max_pools: u32, min_chunk: Uint128, offer_asset: Asset,
+) -> StdResult<Vec<(Addr, Uint128)>> {
+    let pools: Vec<Addr> = pair_config.pool_addresses().into_iter().take(max_pools as usize).collect();
+    let amount = offer_asset.amount.u128();
+    // rounded up so the offer is allocated in at most MAX_SPLIT_CHUNKS chunks
+    let chunk = Uint128::from(amount / MAX_SPLIT_CHUNKS + u128::from(amount % MAX_SPLIT_CHUNKS != 0)).max(min_chunk);
+
+    let mut offers = vec![Uint128::zero(); pools.len()];
+    let mut returns = vec![Uint128::zero(); pools.len()];
+    let mut remaining = offer_asset.amount;
+    while !remaining.is_zero() {
+        let chunk = chunk.min(remaining);
+        // (pool index, return gained by the chunk, return of the pool with the chunk)
+        let mut best: Option<(usize, Uint128, Uint128)> = None;
+        for (i, pool) in pools.iter().enumerate() {
+            let return_amount = query_simulation(querier, &pair_config.with_pool(pool.clone()), Asset {
+                info: offer_asset.info.clone(),
+                amount: offers[i] + chunk,
+            }).map(|res| res.return_amount).unwrap_or_default();
+            let gain = return_amount.saturating_sub(returns[i]);
+            let is_best = match best {
+                Some((_, best_gain, _)) => gain > best_gain,
+                None => true,
+            };
+            if is_best {
+                best = Some((i, gain, return_amount));
+            }
+        }
+        if let Some((i, _, return_amount)) = best {
+            offers[i] += chunk;
+            returns[i] = return_amount;
+        }
+        remaining -= chunk;
+    }
+
+    Ok(pools
+        .into_iter()
+        .zip(offers)
+        .filter(|(_, offer)| !offer.is_zero())
+        .collect())
+}
+
 /// ## Description
 /// Returns information about cumulative prices for the assets in the pool using a [`CumulativePricesResponse`] object.
 /// ## Params
@@ -137,18 +472,154 @@ pub fn query_cumulative_prices(querier: &QuerierWrapper, contract_addr: String)
     Ok(cumulative_prices_response)
 }
 
+/// ## Description
+/// Returns the time-weighted average prices of a pair in a [`TwapResponse`] object.
+/// The averages run from the latest snapshot taken at least `window_seconds` ago to the current cumulative prices.
+pub fn query_twap(deps: Deps, env: Env, asset_infos: [AssetInfo; 2], window_seconds: u64) -> StdResult<TwapResponse> {
+    if window_seconds == 0 || window_seconds > MAX_TWAP_WINDOW {
+        return Err(StdError::generic_err(format!("Window must be within 1 and {} seconds", MAX_TWAP_WINDOW)));
+    }
+    let pair_key = pair_key(&asset_infos);
+    let pair_config = read_pair_config(deps.storage, &asset_infos)?;
+    let end_time = env.block.time.seconds();
+    let snapshot = read_price_snapshot_before(deps.storage, &pair_key, end_time.saturating_sub(window_seconds))?
+        .filter(|snapshot| snapshot.time + window_seconds <= end_time)
+        .ok_or_else(|| StdError::generic_err("No price snapshot old enough for the window"))?;
+
+    // the cumulative prices of the pairs wrap on overflow
+    let prices = query_cumulative_prices(&deps.querier, pair_config.pair_address.to_string())?;
+    let elapsed = end_time - snapshot.time;
+    let average = |cumulative: Uint128, snapshot_cumulative: Uint128| {
+        Decimal::checked_from_ratio(cumulative.wrapping_sub(snapshot_cumulative), elapsed)
+            .map_err(|e| StdError::generic_err(e.to_string()))
+    };
+    Ok(TwapResponse {
+        asset_infos: [prices.assets[0].info.clone(), prices.assets[1].info.clone()],
+        price0_average: average(prices.price0_cumulative_last, snapshot.price0_cumulative)?,
+        price1_average: average(prices.price1_cumulative_last, snapshot.price1_cumulative)?,
+        start_time: snapshot.time,
+        end_time,
+    })
+}
+
+/// ## Description
+/// Returns the route over the enabled registered pairs with the best simulated output in a [`BestRouteResponse`] object.
+/// Routes are limited to the configured max hops and never visit the same asset twice.
+/// ## Params
+/// * **deps** is an object of type [`Deps`].
+///
+/// * **offer_asset** is an object of type [`Asset`]. This is the asset to swap as well as an amount of the said asset.
+///
+/// * **ask_asset_info** is an object of type [`AssetInfo`]. This is the asset to receive.
+pub fn query_best_route(deps: Deps, offer_asset: Asset, ask_asset_info: AssetInfo) -> StdResult<BestRouteResponse> {
+    let config = read_config(deps.storage)?;
+    // the shortest routes are searched first, the search stops once enough candidates are found
+    let mut routes = vec![];
+    for hops in 1..=config.max_hops.min(MAX_HOPS) as usize {
+        collect_routes(deps, &offer_asset.info, &ask_asset_info, hops, &mut vec![], &mut routes)?;
+        if routes.len() >= MAX_ROUTE_CANDIDATES {
+            break;
+        }
+    }
+
+    let mut best_route: Option<BestRouteResponse> = None;
+    for operations in routes {
+        // a route whose pair cannot simulate the amount is skipped
+        let return_amount = match simulate_route(deps, offer_asset.amount, &operations) {
+            Ok(simulations) => simulations.last().map_or(offer_asset.amount, |simulation| simulation.return_amount),
+            Err(_) => continue,
+        };
+        let is_better = match &best_route {
+            Some(best_route) => return_amount > best_route.return_amount,
+            None => true,
+        };
+        if is_better {
+            best_route = Some(BestRouteResponse { operations, return_amount });
+        }
+    }
+
+    best_route.ok_or_else(|| {
+        StdError::generic_err(format!("No route found from {} to {}", offer_asset.info, ask_asset_info))
+    })
+}
+
+/// Collects the routes of exactly `hops` hops over the enabled pairs, walking the pairs of each asset from its indexes
+fn collect_routes(deps: Deps, from: &AssetInfo, to: &AssetInfo, hops: usize,
+                  path: &mut Vec<SwapOperation>, routes: &mut Vec<Vec<SwapOperation>>) -> StdResult<()> {
+    for pair_config in enabled_pairs_of_asset(deps, from)? {
+        if routes.len() >= MAX_ROUTE_CANDIDATES {
+            break;
+        }
+        let next = if pair_config.asset_infos[0].equal(from) {
+            &pair_config.asset_infos[1]
+        } else if pair_config.asset_infos[1].equal(from) {
+            &pair_config.asset_infos[0]
+        } else {
+            continue;
+        };
+        if path.iter().any(|operation| operation.offer_asset_info.equal(next)) {
+            continue;
+        }
+
+        path.push(SwapOperation {
+            offer_asset_info: from.clone(),
+            ask_asset_info: next.clone(),
+        });
+        if path.len() == hops {
+            if next.equal(to) {
+                routes.push(path.clone());
+            }
+        } else if !next.equal(to) {
+            collect_routes(deps, next, to, hops, path, routes)?;
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+/// The enabled pairs trading the asset, on either side
+fn enabled_pairs_of_asset(deps: Deps, asset_info: &AssetInfo) -> StdResult<Vec<PairConfig>> {
+    let pair_configs = pair_configs();
+    let asset_key = asset_info.as_bytes().to_vec();
+    let mut pairs = vec![];
+    for index in [&pair_configs.idx.asset_0, &pair_configs.idx.asset_1] {
+        for item in index.prefix(asset_key.clone()).range(deps.storage, None, None, Order::Ascending) {
+            let (_, pair_config) = item?;
+            if !pair_config.is_disabled {
+                pairs.push(pair_config);
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// Returns the simulations of the hops of a route, each offered the return of the previous one.
+fn simulate_route(deps: Deps, offer_amount: Uint128, operations: &[SwapOperation]) -> StdResult<Vec<SimulationResponse>> {
+    let mut simulations: Vec<SimulationResponse> = vec![];
+    for operation in operations {
+        let pair_config = read_pair_config(deps.storage, &operation.asset_infos())?;
+        let amount = simulations.last().map_or(offer_amount, |simulation| simulation.return_amount);
+        simulations.push(query_simulation(&deps.querier, &pair_config, Asset {
+            info: operation.offer_asset_info.clone(),
+            amount,
+        })?);
+    }
+    Ok(simulations)
+}
+
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::Decimal;
     use super::*;
     use cosmwasm_std::testing::{mock_dependencies};
-    use crate::state::{store_config, store_pair_configs, store_swap_whitelist};
+    use crate::state::{PairType, store_config, store_pair_configs, store_swap_whitelist};
 
     #[test]
     fn test_query_config() {
         let mut deps = mock_dependencies();
         let owner = Addr::unchecked("owner");
-        let config = Config { owner: owner.clone() };
+        let config = Config { owner: owner.clone(), max_hops: 3, paused: false, paused_by: None, fee_bps: 0, fee_collector: None,
+                              max_split_pools: 3, min_split_chunk: Uint128::zero(), oracle: None };
         store_config(&mut deps.storage, &config).unwrap();
         let res = query_config(deps.as_ref()).unwrap();
         assert_eq!(res.owner, owner);
@@ -160,9 +631,9 @@ mod tests {
         let caller = Addr::unchecked("caller");
         store_swap_whitelist(&mut deps.storage, caller.clone(), true).unwrap();
         let res = query_is_swap_whitelist(deps.as_ref(), caller.clone()).unwrap();
-        assert_eq!(res, true);
+        assert!(res);
         let res = query_is_swap_whitelist(deps.as_ref(), Addr::unchecked("other")).unwrap();
-        assert_eq!(res, false);
+        assert!(!res);
     }
 
     #[test]
@@ -178,10 +649,15 @@ mod tests {
         ];
         let pair_key = pair_key(&asset_infos);
         let pair_config = PairConfig {
+            asset_infos: asset_infos.clone(),
             pair_address: Addr::unchecked("pair"),
             is_disabled: true,
-            max_spread: Option::from(Decimal::new(Uint128::from(1000_00u128))),
+            max_spread: Option::from(Decimal::new(Uint128::from(100_000u128))),
             to: None,
+            fee_bps: None,
+            pair_type: PairType::AstroportXyk,
+            pools: vec![],
+            max_oracle_deviation: None,
         };
         store_pair_configs(&mut deps.storage, &pair_key, &pair_config).unwrap();
         // let res = query_pair_config(deps.as_ref(), asset_infos).unwrap();
@@ -1,38 +1,415 @@
+use std::fmt;
+use cosmwasm_schema::cw_serde;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::{Addr, Decimal, StdError, StdResult, Storage, Uint128};
+use cosmwasm_std::{Addr, Decimal, Order, StdError, StdResult, Storage, Uint128};
 
-use cw_storage_plus::{Item, Map};
+use cw_storage_plus::{Bound, Index, IndexList, IndexedMap, Item, Map, MultiIndex};
+
+use crate::error::ContractError;
+use crate::helper::{AssetInfo, pair_key, pair_name};
+use crate::msg::SwapOperation;
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Config {
     pub owner: Addr,
+    /// Max number of hops of a swap route, the configs stored before it was added get the default
+    #[serde(default = "default_max_hops")]
+    pub max_hops: u32,
+    /// All swaps are rejected while paused
+    #[serde(default)]
+    pub paused: bool,
+    pub paused_by: Option<Addr>,
+    /// Router fee charged on the offer amount of each swap, in basis points
+    #[serde(default)]
+    pub fee_bps: u16,
+    /// Receives the router fee, no fee is charged while unset
+    pub fee_collector: Option<Addr>,
+    /// Max number of pools a split swap is divided across
+    #[serde(default = "default_max_split_pools")]
+    pub max_split_pools: u32,
+    /// Min amount offered to a pool by a split swap
+    #[serde(default)]
+    pub min_split_chunk: Uint128,
+    /// Quotes the prices the pairs are checked against, no check while unset
+    pub oracle: Option<Addr>,
+}
+
+impl Config {
+    /// The router fee charged on an offer amount swapped through the pair
+    pub fn fee_amount(&self, pair_config: &PairConfig, offer_amount: Uint128) -> Uint128 {
+        if self.fee_collector.is_none() {
+            return Uint128::zero();
+        }
+        let fee_bps = pair_config.fee_bps.unwrap_or(self.fee_bps);
+        offer_amount.multiply_ratio(fee_bps, FEE_BPS_DENOMINATOR)
+    }
+
+    /// The smallest offer amount leaving at least `amount` to the pair once the router fee is deducted
+    pub fn amount_with_fee(&self, pair_config: &PairConfig, amount: Uint128) -> Uint128 {
+        if self.fee_collector.is_none() {
+            return amount;
+        }
+        let fee_bps = pair_config.fee_bps.unwrap_or(self.fee_bps);
+        let offer_amount = amount.multiply_ratio(FEE_BPS_DENOMINATOR, FEE_BPS_DENOMINATOR - fee_bps);
+        // the ratio is rounded down, one more unit covers the rounding
+        if offer_amount - self.fee_amount(pair_config, offer_amount) < amount {
+            offer_amount + Uint128::one()
+        } else {
+            offer_amount
+        }
+    }
+}
+
+/// Roles delegating part of the owner permissions.
+#[cw_serde]
+#[derive(Copy)]
+pub enum Role {
+    /// Holds every role below and updates the router settings
+    Admin,
+    /// Registers pairs and updates their max spread and status
+    PairManager,
+    /// Disables pairs
+    Guardian,
+    /// Updates the swap whitelist
+    WhitelistManager,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Admin => "admin",
+            Role::PairManager => "pair_manager",
+            Role::Guardian => "guardian",
+            Role::WhitelistManager => "whitelist_manager",
+        }
+    }
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Interface of a pair contract, selects the shape of the messages sent to the pair.
+#[cw_serde]
+#[derive(Copy)]
+pub enum PairType {
+    /// Astroport constant product pairs, the sparrow swap pairs share their interface
+    AstroportXyk,
+    /// Astroport stable pairs, the messages name the ask asset of the pool
+    AstroportStable,
+    Terraswap,
+    /// Swaps through the dex module of the chain, not supported yet
+    NativeDex,
+}
+
+impl Default for PairType {
+    fn default() -> Self {
+        PairType::AstroportXyk
+    }
+}
+
+impl PairType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PairType::AstroportXyk => "astroport_xyk",
+            PairType::AstroportStable => "astroport_stable",
+            PairType::Terraswap => "terraswap",
+            PairType::NativeDex => "native_dex",
+        }
+    }
+}
+
+impl fmt::Display for PairType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// Length of the buckets of the volume history.
+#[cw_serde]
+#[derive(Copy)]
+pub enum Granularity {
+    Hourly,
+    Daily,
+}
+
+impl Granularity {
+    pub fn seconds(&self) -> u64 {
+        match self {
+            Granularity::Hourly => 3_600,
+            Granularity::Daily => 86_400,
+        }
+    }
+
+    /// Start of the bucket holding the time, in seconds
+    pub fn bucket_start(&self, time: u64) -> u64 {
+        time - time % self.seconds()
+    }
+
+    fn volumes(&self) -> Map<'static, (&'static [u8], u64), VolumeBucket> {
+        match self {
+            Granularity::Hourly => HOURLY_VOLUMES,
+            Granularity::Daily => DAILY_VOLUMES,
+        }
+    }
+}
+
+/// Swap volume of a pair within a bucket of the volume history.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct VolumeBucket {
+    pub swap_count: u64,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+}
+
+/// Cumulative prices of a pair at a time, the time-weighted average prices are derived from two snapshots.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PriceSnapshot {
+    /// Block time of the snapshot, in seconds
+    pub time: u64,
+    pub price0_cumulative: Uint128,
+    pub price1_cumulative: Uint128,
+}
+
+/// Limits on the swaps offering an asset, the volumes are measured in the offer asset.
+#[cw_serde]
+pub struct RateLimit {
+    pub max_swap_amount: Option<Uint128>,
+    /// Max volume of a caller within the window
+    pub max_caller_volume: Option<Uint128>,
+    /// Max volume of a pair within the window
+    pub max_pair_volume: Option<Uint128>,
+    pub window_seconds: u64,
+}
+
+/// Volume swapped within a sliding window, the previous window is weighted by its overlap with the sliding one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct WindowVolume {
+    /// Start of the current fixed window, in seconds
+    pub window_start: u64,
+    pub current: Uint128,
+    pub previous: Uint128,
+}
+
+impl WindowVolume {
+    /// Move to the fixed window holding the time
+    pub fn roll(&mut self, time: u64, window_seconds: u64) {
+        let window_start = time - time % window_seconds;
+        if window_start == self.window_start {
+            return;
+        }
+        self.previous = if window_start == self.window_start + window_seconds { self.current } else { Uint128::zero() };
+        self.current = Uint128::zero();
+        self.window_start = window_start;
+    }
+
+    /// Volume within the sliding window ending at the time, after rolling to it
+    pub fn volume(&self, time: u64, window_seconds: u64) -> Uint128 {
+        let elapsed = time - self.window_start;
+        self.previous.multiply_ratio(window_seconds - elapsed, window_seconds) + self.current
+    }
+}
+
+/// A pending transfer of the contract ownership, claimed by the proposed owner before `ttl`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct OwnershipProposal {
+    pub owner: Addr,
+    /// Expiration time of the proposal, in seconds
+    pub ttl: u64,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct PairConfig {
+    pub asset_infos: [AssetInfo; 2],
     pub pair_address: Addr,
     pub is_disabled: bool,
     pub max_spread: Option<Decimal>,
     /// None. default sender
     pub to: Option<Addr>,
+    /// Overrides the router fee of the config for this pair
+    pub fee_bps: Option<u16>,
+    /// Pairs registered before the pair types were added are Astroport pairs
+    #[serde(default)]
+    pub pair_type: PairType,
+    /// Additional pools of the same type trading the assets, only used by split swaps
+    #[serde(default)]
+    pub pools: Vec<Addr>,
+    /// Max deviation of the simulated price of a swap from the oracle price
+    #[serde(default)]
+    pub max_oracle_deviation: Option<Decimal>,
+}
+
+impl PairConfig {
+    /// The pair address followed by the additional pools
+    pub fn pool_addresses(&self) -> Vec<Addr> {
+        let mut pools = vec![self.pair_address.clone()];
+        pools.extend(self.pools.iter().cloned());
+        pools
+    }
+
+    /// The config of the pair with another pool as its address
+    pub fn with_pool(&self, pool_address: Addr) -> PairConfig {
+        PairConfig {
+            pair_address: pool_address,
+            ..self.clone()
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct SwapInfo {
     pub total_amount_in: Uint128,
     pub total_amount_out: Uint128,
+    /// Spread reported by the pair, zero for the swaps recorded before it was tracked
+    #[serde(default)]
+    pub total_spread_amount: Uint128,
+    #[serde(default)]
+    pub total_commission_amount: Uint128,
+    /// Router fee deducted from the offer amount
+    #[serde(default)]
+    pub total_fee_amount: Uint128,
+}
+
+/// Swaps of a caller through a pair, for billing and monitoring the integrators.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CallerStats {
+    pub asset_infos: [AssetInfo; 2],
+    pub swap_count: u64,
+    pub total_amount_in: Uint128,
+    pub total_amount_out: Uint128,
+    pub last_swap_height: u64,
+    /// Block time of the last swap, in seconds
+    pub last_swap_time: u64,
+}
+
+/// In-flight state of a multi-hop swap, kept between the submessage replies of each hop.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwapRouteState {
+    pub sender: Addr,
+    pub to: Addr,
+    pub operations: Vec<SwapOperation>,
+    /// Index of the operation currently being executed
+    pub hop_index: u32,
+    /// Amount offered to the pair of the current operation
+    pub offer_amount: Uint128,
+    /// Router balance of the current ask asset before the hop was executed
+    pub ask_balance_before: Uint128,
+    pub minimum_receive: Option<Uint128>,
+    /// Price the sender expects from the pair, only set for single hop swaps
+    pub belief_price: Option<Decimal>,
+    /// Max spread requested by the sender, clamped by the max spread of each pair
+    pub max_spread: Option<Decimal>,
+    /// Offer amount per pool of the first hop when the swap is split, empty otherwise
+    pub split_offers: Vec<(Addr, Uint128)>,
+    /// Replies of the current hop still expected, one per pool
+    pub pending_replies: u32,
+    /// Spread and commission reported by the pools of the current hop so far
+    pub hop_spread_amount: Uint128,
+    pub hop_commission_amount: Uint128,
+}
+
+/// Default max number of hops of a swap route
+pub const DEFAULT_MAX_HOPS: u32 = 3;
+/// Upper bound of the max number of hops, the route search grows exponentially with it
+pub const MAX_HOPS: u32 = 4;
+
+/// Default max number of pools of a split swap
+pub const DEFAULT_MAX_SPLIT_POOLS: u32 = 3;
+
+fn default_max_hops() -> u32 {
+    DEFAULT_MAX_HOPS
+}
+
+fn default_max_split_pools() -> u32 {
+    DEFAULT_MAX_SPLIT_POOLS
+}
+
+pub const FEE_BPS_DENOMINATOR: u16 = 10_000;
+/// Max router fee, 10%
+pub const MAX_FEE_BPS: u16 = 1_000;
+
+/// Secondary indexes of the pair configs, one per asset position so a pair can be found by either asset.
+pub struct PairConfigIndexes<'a> {
+    pub asset_0: MultiIndex<'a, Vec<u8>, PairConfig, &'a [u8]>,
+    pub asset_1: MultiIndex<'a, Vec<u8>, PairConfig, &'a [u8]>,
+}
+
+impl<'a> IndexList<PairConfig> for PairConfigIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<PairConfig>> + '_> {
+        let v: Vec<&dyn Index<PairConfig>> = vec![&self.asset_0, &self.asset_1];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn pair_configs<'a>() -> IndexedMap<'a, &'a [u8], PairConfig, PairConfigIndexes<'a>> {
+    let indexes = PairConfigIndexes {
+        asset_0: MultiIndex::new(
+            |_pk, pair_config| pair_config.asset_infos[0].as_bytes().to_vec(),
+            "pair_configs",
+            "pair_configs__asset_0",
+        ),
+        asset_1: MultiIndex::new(
+            |_pk, pair_config| pair_config.asset_infos[1].as_bytes().to_vec(),
+            "pair_configs",
+            "pair_configs__asset_1",
+        ),
+    };
+    IndexedMap::new("pair_configs", indexes)
+}
+
+/// A pair config stored before the assets of the pair were recorded with it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct LegacyPairConfig {
+    pub pair_address: Addr,
+    pub is_disabled: bool,
+    pub max_spread: Option<Decimal>,
+    pub to: Option<Addr>,
 }
 
-pub const PAIR_CONFIGS: Map<&[u8], PairConfig> = Map::new("pair_configs");
+/// The pair configs as stored before the asset indexes, under the same namespace
+pub const LEGACY_PAIR_CONFIGS: Map<&[u8], LegacyPairConfig> = Map::new("pair_configs");
 
 pub const CONFIG: Item<Config> = Item::new("config");
 
+pub const OWNERSHIP_PROPOSAL: Item<OwnershipProposal> = Item::new("ownership_proposal");
+
+/// Max lifetime of an ownership proposal, 14 days
+pub const MAX_PROPOSAL_TTL: u64 = 1_209_600;
+
 pub const SWAP_INFOS: Map<&[u8], SwapInfo> = Map::new("swap_infos");
 
 pub const SWAP_WHITELIST: Map<Addr, bool> = Map::new("swap_whitelist");
 
+/// (role, holder) => granted
+pub const ROLES: Map<(&str, &Addr), bool> = Map::new("roles");
+
+/// (caller, pair key) => stats
+pub const CALLER_STATS: Map<(&Addr, &[u8]), CallerStats> = Map::new("caller_stats");
+
+/// (pair key, bucket start) => volume of the bucket
+pub const HOURLY_VOLUMES: Map<(&[u8], u64), VolumeBucket> = Map::new("hourly_volumes");
+pub const DAILY_VOLUMES: Map<(&[u8], u64), VolumeBucket> = Map::new("daily_volumes");
+
+/// offer asset => limits
+pub const RATE_LIMITS: Map<&str, RateLimit> = Map::new("rate_limits");
+/// (caller, offer asset) => volume
+pub const CALLER_VOLUMES: Map<(&Addr, &str), WindowVolume> = Map::new("caller_volumes");
+/// (pair key, offer asset) => volume
+pub const PAIR_VOLUMES: Map<(&[u8], &str), WindowVolume> = Map::new("pair_volumes");
+
+/// (pair key, time) => cumulative prices of the pair
+pub const PRICE_SNAPSHOTS: Map<(&[u8], u64), PriceSnapshot> = Map::new("price_snapshots");
+/// Max window of a time-weighted average price, 7 days, older snapshots are pruned
+pub const MAX_TWAP_WINDOW: u64 = 604_800;
+/// Max snapshots pruned when a snapshot is stored
+const MAX_PRUNED_SNAPSHOTS: usize = 10;
+
+pub const SWAP_ROUTE_STATE: Item<SwapRouteState> = Item::new("swap_route_state");
+
 pub fn store_config(storage: &mut dyn Storage, config: &Config) -> StdResult<()> {
     CONFIG.save(storage, config)?;
     Ok(())
@@ -42,22 +419,61 @@ pub fn read_config(storage: &dyn Storage) -> StdResult<Config> {
     CONFIG.load(storage)
 }
 
+pub fn store_ownership_proposal(storage: &mut dyn Storage, proposal: &OwnershipProposal) -> StdResult<()> {
+    OWNERSHIP_PROPOSAL.save(storage, proposal)?;
+    Ok(())
+}
+
+pub fn read_ownership_proposal(storage: &dyn Storage) -> StdResult<Option<OwnershipProposal>> {
+    OWNERSHIP_PROPOSAL.may_load(storage)
+}
+
+pub fn remove_ownership_proposal(storage: &mut dyn Storage) {
+    OWNERSHIP_PROPOSAL.remove(storage)
+}
+
 pub fn store_pair_configs(
     storage: &mut dyn Storage,
     pair_key: &[u8],
     pair_config: &PairConfig,
 ) -> Result<PairConfig, StdError> {
-    PAIR_CONFIGS.update(storage, pair_key, |old| match old {
+    pair_configs().update(storage, pair_key, |old| match old {
         Some(_) => Ok(pair_config.clone()),
         None => Ok(pair_config.clone()),
     })
 }
 
-pub fn read_pair_config(storage: &dyn Storage, pair_key: &[u8]) -> Result<PairConfig, StdError> {
-    let pair_config = PAIR_CONFIGS
-        .may_load(storage, pair_key)?
-        .ok_or_else(|| StdError::generic_err("Pair config not found"));
-    Ok(pair_config.unwrap())
+/// Rewrites a legacy pair config with its assets, which adds it to the asset indexes.
+/// Returns false when the pair config is already up to date
+pub fn migrate_pair_config(storage: &mut dyn Storage, asset_infos: &[AssetInfo; 2]) -> Result<bool, ContractError> {
+    let pair_key = pair_key(asset_infos);
+    if let Ok(Some(_)) = pair_configs().may_load(storage, &pair_key) {
+        return Ok(false);
+    }
+    let legacy = LEGACY_PAIR_CONFIGS
+        .may_load(storage, &pair_key)?
+        .ok_or_else(|| ContractError::PairNotFound { pair: pair_name(asset_infos) })?;
+
+    // the legacy config does not decode as a pair config, the indexed map could not replace it
+    LEGACY_PAIR_CONFIGS.remove(storage, &pair_key);
+    pair_configs().save(storage, &pair_key, &PairConfig {
+        asset_infos: asset_infos.clone(),
+        pair_address: legacy.pair_address,
+        is_disabled: legacy.is_disabled,
+        max_spread: legacy.max_spread,
+        to: legacy.to,
+        fee_bps: None,
+        pair_type: PairType::default(),
+        pools: vec![],
+        max_oracle_deviation: None,
+    })?;
+    Ok(true)
+}
+
+pub fn read_pair_config(storage: &dyn Storage, asset_infos: &[AssetInfo; 2]) -> Result<PairConfig, ContractError> {
+    pair_configs()
+        .may_load(storage, &pair_key(asset_infos))?
+        .ok_or_else(|| ContractError::PairNotFound { pair: pair_name(asset_infos) })
 }
 
 pub fn store_swap_infos(
@@ -69,11 +485,10 @@ pub fn store_swap_infos(
     Ok(())
 }
 
-pub fn read_swap_info(storage: &dyn Storage, pair_key: &[u8]) -> Result<SwapInfo, StdError> {
-    let swap_info = SWAP_INFOS
-        .may_load(storage, pair_key)?
-        .ok_or_else(|| StdError::generic_err("Swap not found"));
-    Ok(swap_info.unwrap())
+pub fn read_swap_info(storage: &dyn Storage, asset_infos: &[AssetInfo; 2]) -> Result<SwapInfo, ContractError> {
+    SWAP_INFOS
+        .may_load(storage, &pair_key(asset_infos))?
+        .ok_or_else(|| ContractError::SwapInfoNotFound { pair: pair_name(asset_infos) })
 }
 
 pub fn read_swap_info_default_zero(
@@ -85,6 +500,9 @@ pub fn read_swap_info_default_zero(
         SwapInfo {
             total_amount_in: Uint128::zero(),
             total_amount_out: Uint128::zero(),
+            total_spread_amount: Uint128::zero(),
+            total_commission_amount: Uint128::zero(),
+            total_fee_amount: Uint128::zero(),
         }
     }))
 }
@@ -99,13 +517,172 @@ pub fn store_swap_whitelist(
 }
 
 pub fn read_swap_whitelist(storage: &dyn Storage, caller: Addr) -> Result<bool, StdError> {
-    let is_whitelist = SWAP_WHITELIST
-        .may_load(storage, caller)?
-        .ok_or_else(|| StdError::generic_err("Swap not found"));
-    Ok(is_whitelist.unwrap())
+    let is_whitelist = SWAP_WHITELIST.may_load(storage, caller)?;
+    Ok(is_whitelist.unwrap_or(false))
+}
+
+pub fn read_swap_whitelists(
+    storage: &dyn Storage,
+    start_after: Option<Addr>,
+    limit: usize,
+) -> StdResult<Vec<(Addr, bool)>> {
+    let start = start_after.map(Bound::exclusive);
+    SWAP_WHITELIST
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
 }
 
 pub fn is_address_in_whitelist(storage: &dyn Storage, addr: Addr) -> StdResult<bool> {
     let result = SWAP_WHITELIST.may_load(storage, addr)?;
     Ok(result.unwrap_or(false))
 }
+
+pub fn store_swap_route_state(storage: &mut dyn Storage, state: &SwapRouteState) -> StdResult<()> {
+    SWAP_ROUTE_STATE.save(storage, state)?;
+    Ok(())
+}
+
+pub fn read_swap_route_state(storage: &dyn Storage) -> StdResult<SwapRouteState> {
+    SWAP_ROUTE_STATE.load(storage)
+}
+
+pub fn remove_swap_route_state(storage: &mut dyn Storage) {
+    SWAP_ROUTE_STATE.remove(storage)
+}
+
+pub fn store_role(storage: &mut dyn Storage, role: Role, holder: &Addr) -> StdResult<()> {
+    ROLES.save(storage, (role.as_str(), holder), &true)?;
+    Ok(())
+}
+
+pub fn remove_role(storage: &mut dyn Storage, role: Role, holder: &Addr) {
+    ROLES.remove(storage, (role.as_str(), holder))
+}
+
+pub fn has_role(storage: &dyn Storage, role: Role, holder: &Addr) -> StdResult<bool> {
+    Ok(ROLES.may_load(storage, (role.as_str(), holder))?.unwrap_or(false))
+}
+
+pub fn store_caller_stats(storage: &mut dyn Storage, caller: &Addr, pair_key: &[u8], stats: &CallerStats) -> StdResult<()> {
+    CALLER_STATS.save(storage, (caller, pair_key), stats)
+}
+
+pub fn read_caller_stats(storage: &dyn Storage, caller: &Addr, pair_key: &[u8]) -> StdResult<Option<CallerStats>> {
+    CALLER_STATS.may_load(storage, (caller, pair_key))
+}
+
+pub fn read_caller_stats_list(
+    storage: &dyn Storage,
+    caller: &Addr,
+    start_after: Option<Vec<u8>>,
+    limit: usize,
+) -> StdResult<Vec<CallerStats>> {
+    let start = start_after.as_deref().map(Bound::exclusive);
+    CALLER_STATS
+        .prefix(caller)
+        .range(storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, stats)| stats))
+        .collect()
+}
+
+/// Add a swap to the hourly and daily buckets holding the time
+pub fn add_swap_volume(storage: &mut dyn Storage, pair_key: &[u8], time: u64,
+                       amount_in: Uint128, amount_out: Uint128) -> StdResult<()> {
+    for granularity in [Granularity::Hourly, Granularity::Daily] {
+        let key = (pair_key, granularity.bucket_start(time));
+        let mut bucket = granularity.volumes().may_load(storage, key)?.unwrap_or(VolumeBucket {
+            swap_count: 0,
+            amount_in: Uint128::zero(),
+            amount_out: Uint128::zero(),
+        });
+        bucket.swap_count += 1;
+        bucket.amount_in += amount_in;
+        bucket.amount_out += amount_out;
+        granularity.volumes().save(storage, key, &bucket)?;
+    }
+    Ok(())
+}
+
+/// The non-empty buckets of a pair starting within `[from, to)`, ordered by time
+pub fn read_volume_buckets(storage: &dyn Storage, pair_key: &[u8], granularity: Granularity,
+                           from: u64, to: u64, limit: usize) -> StdResult<Vec<(u64, VolumeBucket)>> {
+    granularity
+        .volumes()
+        .prefix(pair_key)
+        .range(storage, Some(Bound::inclusive(from)), Some(Bound::exclusive(to)), Order::Ascending)
+        .take(limit)
+        .collect()
+}
+
+/// Store a snapshot of a pair and prune its snapshots older than the max window,
+/// keeping the latest of them so a full window can still be covered
+pub fn store_price_snapshot(storage: &mut dyn Storage, pair_key: &[u8], snapshot: &PriceSnapshot) -> StdResult<()> {
+    PRICE_SNAPSHOTS.save(storage, (pair_key, snapshot.time), snapshot)?;
+
+    let cutoff = snapshot.time.saturating_sub(MAX_TWAP_WINDOW);
+    let expired = PRICE_SNAPSHOTS
+        .prefix(pair_key)
+        .keys(storage, None, Some(Bound::exclusive(cutoff)), Order::Ascending)
+        .take(MAX_PRUNED_SNAPSHOTS + 1)
+        .collect::<StdResult<Vec<u64>>>()?;
+    for time in expired.iter().take(expired.len().saturating_sub(1)) {
+        PRICE_SNAPSHOTS.remove(storage, (pair_key, *time));
+    }
+    Ok(())
+}
+
+/// The latest snapshot of a pair taken at or before the time
+pub fn read_price_snapshot_before(storage: &dyn Storage, pair_key: &[u8], time: u64) -> StdResult<Option<PriceSnapshot>> {
+    PRICE_SNAPSHOTS
+        .prefix(pair_key)
+        .range(storage, None, Some(Bound::inclusive(time)), Order::Descending)
+        .next()
+        .map(|item| item.map(|(_, snapshot)| snapshot))
+        .transpose()
+}
+
+pub fn store_rate_limit(storage: &mut dyn Storage, offer_asset: &AssetInfo, rate_limit: &RateLimit) -> StdResult<()> {
+    RATE_LIMITS.save(storage, &offer_asset.to_string(), rate_limit)
+}
+
+pub fn remove_rate_limit(storage: &mut dyn Storage, offer_asset: &AssetInfo) {
+    RATE_LIMITS.remove(storage, &offer_asset.to_string())
+}
+
+pub fn read_rate_limit(storage: &dyn Storage, offer_asset: &AssetInfo) -> StdResult<Option<RateLimit>> {
+    RATE_LIMITS.may_load(storage, &offer_asset.to_string())
+}
+
+pub fn read_caller_volume(storage: &dyn Storage, caller: &Addr, offer_asset: &AssetInfo) -> StdResult<WindowVolume> {
+    Ok(CALLER_VOLUMES.may_load(storage, (caller, &offer_asset.to_string()))?.unwrap_or_default())
+}
+
+pub fn store_caller_volume(storage: &mut dyn Storage, caller: &Addr, offer_asset: &AssetInfo,
+                           volume: &WindowVolume) -> StdResult<()> {
+    CALLER_VOLUMES.save(storage, (caller, &offer_asset.to_string()), volume)
+}
+
+pub fn read_pair_volume(storage: &dyn Storage, pair_key: &[u8], offer_asset: &AssetInfo) -> StdResult<WindowVolume> {
+    Ok(PAIR_VOLUMES.may_load(storage, (pair_key, &offer_asset.to_string()))?.unwrap_or_default())
+}
+
+pub fn store_pair_volume(storage: &mut dyn Storage, pair_key: &[u8], offer_asset: &AssetInfo,
+                         volume: &WindowVolume) -> StdResult<()> {
+    PAIR_VOLUMES.save(storage, (pair_key, &offer_asset.to_string()), volume)
+}
+
+pub fn read_role_holders(
+    storage: &dyn Storage,
+    role: Role,
+    start_after: Option<Addr>,
+    limit: usize,
+) -> StdResult<Vec<Addr>> {
+    let start = start_after.as_ref().map(Bound::exclusive);
+    ROLES
+        .prefix(role.as_str())
+        .keys(storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect()
+}
@@ -1,13 +1,14 @@
 use cosmwasm_std::{Addr, Env, MessageInfo, OwnedDeps, Response, StdResult};
-use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MockApi, MockQuerier, MockStorage};
+use cosmwasm_std::testing::{mock_env, mock_info, MockApi, MockStorage};
 use crate::contract::instantiate;
 use crate::msg::InstantiateMsg;
+use crate::testing::mock_querier::{mock_dependencies_with_querier, WasmMockQuerier};
 
 pub const CREATOR: &str = "creator";
 
 pub fn mock_instantiate_msg() -> InstantiateMsg {
     InstantiateMsg {
-        owner: Addr::unchecked(CREATOR.clone().to_string()),
+        owner: Addr::unchecked(CREATOR.to_string()),
     }
 }
 
@@ -15,12 +16,12 @@ pub fn mock_instantiate_msg() -> InstantiateMsg {
 pub fn mock_instantiate(
     msg: InstantiateMsg,
 ) -> (
-    OwnedDeps<MockStorage, MockApi, MockQuerier>,
+    OwnedDeps<MockStorage, MockApi, WasmMockQuerier>,
     Env,
     MessageInfo,
     StdResult<Response>,
 ) {
-    let mut deps = mock_dependencies();
+    let mut deps = mock_dependencies_with_querier(&[]);
     let env = mock_env();
     let info = mock_info(CREATOR, &[]);
     let res = instantiate(deps.as_mut(), env.clone(), info.clone(), msg);
@@ -0,0 +1,247 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use cosmwasm_std::{Addr, Coin, ContractResult, Decimal, Decimal256, Empty, from_binary, from_slice, OwnedDeps, Querier, QuerierResult, QueryRequest, SystemError, SystemResult, to_binary, Uint128, WasmQuery};
+use cosmwasm_std::testing::{MOCK_CONTRACT_ADDR, MockApi, MockQuerier, MockStorage};
+use cw20::{BalanceResponse as Cw20BalanceResponse, Cw20QueryMsg};
+use crate::helper::{Asset, AssetInfo};
+use crate::msg::{CumulativePricesResponse, OracleQueryMsg, OraclePriceResponse, PairInfo, ReverseSimulationResponse, SimulationResponse, StableQueryMsg, SwapQueryMsg};
+
+/// Mock dependencies whose querier answers the simulations of mock pairs
+/// and whose balances can be updated between the submessages of a swap.
+pub fn mock_dependencies_with_querier(
+    contract_balance: &[Coin],
+) -> OwnedDeps<MockStorage, MockApi, WasmMockQuerier> {
+    let custom_querier =
+        WasmMockQuerier::new(MockQuerier::new(&[(MOCK_CONTRACT_ADDR, contract_balance)]));
+
+    OwnedDeps {
+        storage: MockStorage::default(),
+        api: MockApi::default(),
+        querier: custom_querier,
+        custom_query_type: PhantomData,
+    }
+}
+
+pub struct WasmMockQuerier {
+    base: MockQuerier<Empty>,
+    /// (pair address, offer asset) => return amount per offer unit
+    pair_rates: HashMap<(String, String), Decimal>,
+    /// (pair address, ask asset) => offer amount per ask unit
+    reverse_rates: HashMap<(String, String), Decimal>,
+    /// (pair address, offer asset) => (offer reserve, ask reserve) of a constant product pool
+    pair_reserves: HashMap<(String, String), (Uint128, Uint128)>,
+    /// pair address => traded assets
+    pair_infos: HashMap<String, Vec<AssetInfo>>,
+    /// pair address => cumulative prices
+    cumulative_prices: HashMap<String, CumulativePricesResponse>,
+    /// oracle address => asset => price
+    oracle_prices: HashMap<String, HashMap<String, Decimal256>>,
+    /// token address => account address => balance
+    token_balances: HashMap<String, HashMap<String, Uint128>>,
+}
+
+impl Querier for WasmMockQuerier {
+    fn raw_query(&self, bin_request: &[u8]) -> QuerierResult {
+        let request: QueryRequest<Empty> = match from_slice(bin_request) {
+            Ok(v) => v,
+            Err(e) => {
+                return SystemResult::Err(SystemError::InvalidRequest {
+                    error: format!("Parsing query request: {}", e),
+                    request: bin_request.into(),
+                });
+            }
+        };
+        self.handle_query(&request)
+    }
+}
+
+impl WasmMockQuerier {
+    pub fn new(base: MockQuerier<Empty>) -> Self {
+        WasmMockQuerier {
+            base,
+            pair_rates: HashMap::new(),
+            pair_reserves: HashMap::new(),
+            reverse_rates: HashMap::new(),
+            pair_infos: HashMap::new(),
+            token_balances: HashMap::new(),
+            oracle_prices: HashMap::new(),
+            cumulative_prices: HashMap::new(),
+        }
+    }
+
+    pub fn handle_query(&self, request: &QueryRequest<Empty>) -> QuerierResult {
+        match request {
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg })
+                if self.token_balances.contains_key(contract_addr) =>
+            {
+                match from_binary(msg) {
+                    Ok(Cw20QueryMsg::Balance { address }) => {
+                        let balance = self.token_balances[contract_addr]
+                            .get(&address)
+                            .cloned()
+                            .unwrap_or_default();
+                        SystemResult::Ok(ContractResult::Ok(
+                            to_binary(&Cw20BalanceResponse { balance }).unwrap(),
+                        ))
+                    }
+                    _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                        kind: contract_addr.clone(),
+                    }),
+                }
+            }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg })
+                if self.oracle_prices.contains_key(contract_addr) =>
+            {
+                match from_binary(msg) {
+                    Ok(OracleQueryMsg::QueryPrice { asset }) => match self.oracle_prices[contract_addr].get(&asset) {
+                        Some(price) => SystemResult::Ok(ContractResult::Ok(
+                            to_binary(&OraclePriceResponse { asset, price: *price }).unwrap(),
+                        )),
+                        None => SystemResult::Ok(ContractResult::Err("Asset is not valid".to_string())),
+                    },
+                    _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                        kind: contract_addr.clone(),
+                    }),
+                }
+            }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg })
+                if from_binary::<StableQueryMsg>(msg).is_ok() =>
+            {
+                match from_binary(msg) {
+                    Ok(StableQueryMsg::Simulation { offer_asset, .. }) => self.simulate(contract_addr, offer_asset),
+                    Ok(StableQueryMsg::ReverseSimulation { ask_asset, .. }) => self.reverse_simulate(contract_addr, ask_asset),
+                    _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                        kind: contract_addr.clone(),
+                    }),
+                }
+            }
+            QueryRequest::Wasm(WasmQuery::Smart { contract_addr, msg }) => match from_binary(msg) {
+                Ok(SwapQueryMsg::Simulation { offer_asset }) => self.simulate(contract_addr, offer_asset),
+                Ok(SwapQueryMsg::ReverseSimulation { ask_asset }) => self.reverse_simulate(contract_addr, ask_asset),
+                Ok(SwapQueryMsg::CumulativePrices {}) => match self.cumulative_prices.get(contract_addr) {
+                    Some(prices) => SystemResult::Ok(ContractResult::Ok(to_binary(prices).unwrap())),
+                    None => SystemResult::Err(SystemError::UnsupportedRequest {
+                        kind: contract_addr.clone(),
+                    }),
+                },
+                Ok(SwapQueryMsg::Pair {}) => match self.pair_infos.get(contract_addr) {
+                    Some(asset_infos) => SystemResult::Ok(ContractResult::Ok(
+                        to_binary(&PairInfo {
+                            asset_infos: asset_infos.clone(),
+                            contract_addr: Addr::unchecked(contract_addr),
+                        })
+                        .unwrap(),
+                    )),
+                    None => SystemResult::Err(SystemError::NoSuchContract {
+                        addr: contract_addr.clone(),
+                    }),
+                },
+                _ => SystemResult::Err(SystemError::UnsupportedRequest {
+                    kind: contract_addr.clone(),
+                }),
+            },
+            _ => self.base.handle_query(request),
+        }
+    }
+
+    fn simulate(&self, contract_addr: &str, offer_asset: Asset) -> QuerierResult {
+        let key = (contract_addr.to_string(), offer_asset.info.to_string());
+        if let Some((offer_reserve, ask_reserve)) = self.pair_reserves.get(&key) {
+            let return_amount = ask_reserve.multiply_ratio(offer_asset.amount, *offer_reserve + offer_asset.amount);
+            return SystemResult::Ok(ContractResult::Ok(
+                to_binary(&SimulationResponse {
+                    return_amount,
+                    // the shortfall from the spot price of the reserves
+                    spread_amount: ask_reserve.multiply_ratio(offer_asset.amount, *offer_reserve) - return_amount,
+                    commission_amount: Uint128::zero(),
+                })
+                .unwrap(),
+            ));
+        }
+        match self.pair_rates.get(&key) {
+            Some(rate) => SystemResult::Ok(ContractResult::Ok(
+                to_binary(&SimulationResponse {
+                    return_amount: offer_asset.amount * *rate,
+                    spread_amount: Uint128::zero(),
+                    commission_amount: Uint128::zero(),
+                })
+                .unwrap(),
+            )),
+            None => SystemResult::Err(SystemError::NoSuchContract {
+                addr: contract_addr.to_string(),
+            }),
+        }
+    }
+
+    fn reverse_simulate(&self, contract_addr: &str, ask_asset: Asset) -> QuerierResult {
+        let key = (contract_addr.to_string(), ask_asset.info.to_string());
+        match self.reverse_rates.get(&key) {
+            Some(rate) => SystemResult::Ok(ContractResult::Ok(
+                to_binary(&ReverseSimulationResponse {
+                    offer_amount: ask_asset.amount * *rate,
+                    spread_amount: Uint128::zero(),
+                    commission_amount: Uint128::zero(),
+                })
+                .unwrap(),
+            )),
+            None => SystemResult::Err(SystemError::NoSuchContract {
+                addr: contract_addr.to_string(),
+            }),
+        }
+    }
+
+    /// Sets the return amount per offer unit a mock pair quotes for the offer denom.
+    pub fn with_pair_rate(&mut self, pair_address: &str, offer_denom: &str, rate: Decimal) {
+        self.pair_rates
+            .insert((pair_address.to_string(), offer_denom.to_string()), rate);
+    }
+
+    /// Sets the offer amount per ask unit a mock pair quotes for the ask denom in its reverse simulation.
+    pub fn with_pair_reverse_rate(&mut self, pair_address: &str, ask_denom: &str, rate: Decimal) {
+        self.reverse_rates
+            .insert((pair_address.to_string(), ask_denom.to_string()), rate);
+    }
+
+    /// Sets the reserves of a constant product mock pair, its return decreases with the offer amount.
+    pub fn with_pair_reserves(&mut self, pair_address: &str, offer_denom: &str, offer_reserve: Uint128, ask_reserve: Uint128) {
+        self.pair_reserves
+            .insert((pair_address.to_string(), offer_denom.to_string()), (offer_reserve, ask_reserve));
+    }
+
+    /// Sets the assets a mock pair reports in its pair info.
+    pub fn with_pair_info(&mut self, pair_address: &str, asset_infos: &[AssetInfo]) {
+        self.pair_infos.insert(pair_address.to_string(), asset_infos.to_vec());
+    }
+
+    /// Sets the cumulative prices a mock pair reports for its assets.
+    pub fn with_cumulative_prices(&mut self, pair_address: &str, asset_infos: &[AssetInfo; 2],
+                                  price0_cumulative: Uint128, price1_cumulative: Uint128) {
+        let asset = |info: &AssetInfo| Asset { info: info.clone(), amount: Uint128::zero() };
+        self.cumulative_prices.insert(pair_address.to_string(), CumulativePricesResponse {
+            assets: [asset(&asset_infos[0]), asset(&asset_infos[1])],
+            total_share: Uint128::zero(),
+            price0_cumulative_last: price0_cumulative,
+            price1_cumulative_last: price1_cumulative,
+        });
+    }
+
+    /// Sets the price a mock oracle quotes for the asset.
+    pub fn with_oracle_price(&mut self, oracle_address: &str, asset: &str, price: Decimal256) {
+        self.oracle_prices
+            .entry(oracle_address.to_string())
+            .or_default()
+            .insert(asset.to_string(), price);
+    }
+
+    /// Sets the cw20 balance an account holds in a mock token contract.
+    pub fn with_token_balance(&mut self, token_address: &str, account: &str, balance: Uint128) {
+        self.token_balances
+            .entry(token_address.to_string())
+            .or_default()
+            .insert(account.to_string(), balance);
+    }
+
+    pub fn update_balance(&mut self, addr: &str, balance: Vec<Coin>) {
+        self.base.update_balance(addr, balance);
+    }
+}
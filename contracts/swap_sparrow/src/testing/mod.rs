@@ -1,2 +1,3 @@
 mod mock_fn;
-mod tests;
\ No newline at end of file
+mod mock_querier;
+mod tests;
@@ -1,11 +1,34 @@
 use std::str::FromStr;
-use cosmwasm_std::{Addr, Decimal, Uint128};
-use cosmwasm_std::testing::mock_info;
+use cosmwasm_std::{attr, Addr, Attribute, BankMsg, StdError, Storage, from_binary, Coin, CosmosMsg, Decimal, Decimal256, Event, Env, MessageInfo, OwnedDeps, Reply, Response, Timestamp, SubMsg, SubMsgResponse, SubMsgResult, to_binary, Uint128, WasmMsg};
+use cosmwasm_std::testing::{MOCK_CONTRACT_ADDR, mock_info, MockApi, MockStorage};
+use crate::contract::{migrate, query, reply};
 use crate::error::ContractError;
-use crate::handler::{change_owner, set_whitelist, update_pair_config, update_pair_max_spread, update_pair_status};
-use crate::helper::AssetInfo;
-use crate::querier::{query_config, query_is_swap_whitelist, query_pair_config, query_swap_info};
-use crate::testing::mock_fn::{mock_instantiate, mock_instantiate_msg};
+use crate::handler::{add_pair_pool, remove_pair_pool, update_split_config, claim_ownership, drop_ownership_proposal, grant_role, pause, propose_new_owner, receive_cw20, revoke_role, unpause, set_rate_limit, set_whitelist, set_whitelist_batch, snapshot_prices, swap_denom, swap_denom_exact_out, swap_route, SWAP_ROUTE_REPLY_ID, update_fee_config, update_max_hops, update_oracle, update_pair_max_oracle_deviation, update_pair_config, update_pair_fee, update_pair_max_spread, update_pair_status};
+use crate::helper::{Asset, AssetInfo, pair_key};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use crate::msg::{HopQuote, MigrateMsg, SimulationBatchResponse, SimulationBatchResult, Cw20HookMsg, PairCw20HookMsg, QueryMsg, SimulationResponse, StableCw20HookMsg, StableSwapMsg, SwapMsg, SwapOperation};
+use crate::querier::{query_all_pair_configs, query_best_route, query_caller_stats, query_caller_stats_list, query_rate_limit, query_volume_history, query_config, query_is_swap_whitelist, query_pair_config, query_pairs_by_asset, query_role_holders, query_quote, query_swap_info, query_twap, query_whitelist};
+use crate::state::{MAX_HOPS, LEGACY_PAIR_CONFIGS, LegacyPairConfig, DEFAULT_MAX_HOPS, DEFAULT_MAX_SPLIT_POOLS, read_pair_config, read_swap_info, MAX_TWAP_WINDOW, PRICE_SNAPSHOTS, Granularity, MAX_PROPOSAL_TTL, PairType, RateLimit, OwnershipProposal, Role};
+use crate::testing::mock_fn::{CREATOR, mock_instantiate, mock_instantiate_msg};
+use crate::testing::mock_querier::WasmMockQuerier;
+
+fn native(denom: &str) -> AssetInfo {
+    AssetInfo::NativeToken { denom: denom.to_string() }
+}
+
+/// Registers a pair whose mock contract trades the assets
+fn register_pair(deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>, info: MessageInfo, asset_infos: [AssetInfo; 2],
+                 pair_address: Addr, max_spread: Option<Decimal>, to: Option<Addr>) -> Result<Response, ContractError> {
+    deps.querier.with_pair_info(pair_address.as_str(), &asset_infos);
+    update_pair_config(deps.as_mut(), info, asset_infos, pair_address, max_spread, to, PairType::default())
+}
+
+fn swap_reply_ok() -> Reply {
+    Reply {
+        id: SWAP_ROUTE_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse { events: vec![], data: None }),
+    }
+}
 
 #[test]
 fn test_instantiate() {
@@ -18,24 +41,26 @@ fn test_instantiate() {
 #[test]
 fn test_update_config() {
     let msg = mock_instantiate_msg();
-    let (mut deps, _, info, res) = mock_instantiate(msg);
+    let (mut deps, env, info, res) = mock_instantiate(msg);
     assert!(res.is_ok());
     // change owner
     let new_owner = Addr::unchecked("new_owner".to_string());
-    let res = change_owner(deps.as_mut(), info.clone(), new_owner.clone());
+    let res = propose_new_owner(deps.as_mut(), env.clone(), info.clone(), new_owner.to_string(), 100);
     assert!(res.is_ok());
 
     let new_info = mock_info("new_owner", &[]);
+    let res = claim_ownership(deps.as_mut(), env.clone(), new_info.clone());
+    assert!(res.is_ok());
     // update pair config failed
     let asset_infos = [AssetInfo::NativeToken { denom: "sei".to_string() }, AssetInfo::NativeToken { denom: "factory/xxx/kusd".to_string() }];
     let pair_address = Addr::unchecked("pair_address".to_string());
-    let res = update_pair_config(deps.as_mut(), info.clone(),
+    let res = register_pair(&mut deps, info.clone(),
                                  asset_infos.clone(), pair_address.clone(), None, None);
     assert!(res.is_err());
-    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::PairManager });
 
     // success
-    let res = update_pair_config(deps.as_mut(), new_info.clone(),
+    let res = register_pair(&mut deps, new_info.clone(),
                                  asset_infos.clone(), pair_address.clone(), None, None);
     assert!(res.is_ok());
 
@@ -44,7 +69,7 @@ fn test_update_config() {
     let res = update_pair_status(deps.as_mut(), info.clone(),
                                  asset_infos.clone(), false);
     assert!(res.is_err());
-    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::PairManager });
 
     // success
     let res = update_pair_status(deps.as_mut(), new_info.clone(),
@@ -55,7 +80,7 @@ fn test_update_config() {
     let res = update_pair_max_spread(deps.as_mut(), info.clone(),
                                      asset_infos.clone(), Decimal::new(Uint128::from(1000u128)));
     assert!(res.is_err());
-    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::PairManager });
 
     // success
     let res = update_pair_max_spread(deps.as_mut(), new_info.clone(),
@@ -65,19 +90,21 @@ fn test_update_config() {
     // set whitelist failed
     let res = set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("whitelist".to_string()), true);
     assert!(res.is_err());
-    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::WhitelistManager });
 
     // success
     let res = set_whitelist(deps.as_mut(), new_info.clone(), Addr::unchecked("whitelist".to_string()), true);
     assert!(res.is_ok());
 
     //update owner failed
-    let res = change_owner(deps.as_mut(), info.clone(), new_owner.clone());
+    let res = propose_new_owner(deps.as_mut(), env.clone(), info.clone(), new_owner.to_string(), 100);
     assert!(res.is_err());
     assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
 
     // success
-    let res = change_owner(deps.as_mut(), new_info.clone(), Addr::unchecked("new_owner_2".to_string()));
+    let res = propose_new_owner(deps.as_mut(), env.clone(), new_info.clone(), "new_owner_2".to_string(), 100);
+    assert!(res.is_ok());
+    let res = claim_ownership(deps.as_mut(), env.clone(), mock_info("new_owner_2", &[]));
     assert!(res.is_ok());
 
     // query config
@@ -91,7 +118,7 @@ fn test_update_config() {
 
     // query is swap white list
     let query_res = query_is_swap_whitelist(deps.as_ref(), Addr::unchecked("whitelist".to_string())).unwrap();
-    assert_eq!(query_res, true);
+    assert!(query_res);
 
     // let query_res = query_is_swap_whitelist(deps.as_ref(), Addr::unchecked("whitelist2".to_string()));
     // assert!(query_res.is_err());
@@ -99,7 +126,1751 @@ fn test_update_config() {
     // query pair config
     let query_res = query_pair_config(deps.as_ref(),asset_infos.clone()).unwrap();
     assert_eq!(query_res.pair_address, pair_address);
-    assert_eq!(query_res.is_disabled, true);
+    assert!(query_res.is_disabled);
     assert_eq!(query_res.max_spread, Some(Decimal::from_str("0.000000000000001").unwrap()));
     assert_eq!(query_res.to, None);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_swap_route() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("usei"), native("ukusd")],
+                       Addr::unchecked("pair_sei_kusd"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+
+    let operations = vec![
+        SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") },
+        SwapOperation { offer_asset_info: native("usei"), ask_asset_info: native("ukusd") },
+    ];
+    let caller = mock_info("caller", &[Coin::new(100, "uusd")]);
+
+    // not whitelisted
+    let res = swap_route(deps.as_mut(), env.clone(), mock_info("other", &[Coin::new(100, "uusd")]),
+                         operations.clone(), None, None);
+    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+
+    // operations must be chained
+    let res = swap_route(deps.as_mut(), env.clone(), caller.clone(),
+                         vec![operations[1].clone(), operations[0].clone()], None, None);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidSwapOperations {});
+
+    // unregistered hop
+    let res = swap_route(deps.as_mut(), env.clone(), caller.clone(),
+                         vec![SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("ukusd") }], None, None);
+    assert_eq!(res.err().unwrap(), ContractError::PairNotFound { pair: "uusd-ukusd".to_string() });
+
+    // first hop goes to the first pair and returns to the router
+    let res = swap_route(deps.as_mut(), env.clone(), caller.clone(),
+                         operations.clone(), Some(Uint128::from(300u128)), None).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::reply_on_success(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: "pair_usd_sei".to_string(),
+        msg: to_binary(&SwapMsg::Swap {
+            offer_asset: Asset { info: native("uusd"), amount: Uint128::from(100u128) },
+            belief_price: None,
+            max_spread: None,
+            to: Some(MOCK_CONTRACT_ADDR.to_string()),
+        }).unwrap(),
+        funds: vec![Coin::new(100, "uusd")],
+    }), SWAP_ROUTE_REPLY_ID)]);
+
+    // the output of the first hop is offered to the second pair
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(200, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, SWAP_ROUTE_REPLY_ID);
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, funds, .. }) => {
+            assert_eq!(contract_addr, "pair_sei_kusd");
+            assert_eq!(funds, &vec![Coin::new(200, "usei")]);
+        }
+        _ => panic!("unexpected message"),
+    }
+
+    // final output below minimum receive reverts
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(299, "ukusd")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok());
+    assert_eq!(res.err().unwrap(), ContractError::AssertionMinimumReceive {
+        receive: Uint128::from(299u128),
+        minimum_receive: Uint128::from(300u128),
+    });
+
+    // final output is delivered to the caller
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(400, "ukusd")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "caller".to_string(),
+        amount: vec![Coin::new(400, "ukusd")],
+    }))]);
+
+    let query_res = query_swap_info(deps.as_ref(), [native("usei"), native("uusd")]).unwrap();
+    assert_eq!(query_res.total_amount_in, Uint128::from(100u128));
+    assert_eq!(query_res.total_amount_out, Uint128::from(200u128));
+    let query_res = query_swap_info(deps.as_ref(), [native("usei"), native("ukusd")]).unwrap();
+    assert_eq!(query_res.total_amount_in, Uint128::from(200u128));
+    assert_eq!(query_res.total_amount_out, Uint128::from(400u128));
+}
+
+#[test]
+fn test_best_route() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("usei"), native("ukusd")],
+                       Addr::unchecked("pair_sei_kusd"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("ukusd")],
+                       Addr::unchecked("pair_usd_kusd"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    deps.querier.with_pair_rate("pair_usd_sei", "uusd", Decimal::from_str("2").unwrap());
+    deps.querier.with_pair_rate("pair_sei_kusd", "usei", Decimal::from_str("2").unwrap());
+    deps.querier.with_pair_rate("pair_usd_kusd", "uusd", Decimal::from_str("3").unwrap());
+    let offer_asset = Asset { info: native("uusd"), amount: Uint128::from(100u128) };
+
+    // the two hop route beats the direct pair
+    let res = query_best_route(deps.as_ref(), offer_asset.clone(), native("ukusd")).unwrap();
+    assert_eq!(res.return_amount, Uint128::from(400u128));
+    assert_eq!(res.operations, vec![
+        SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") },
+        SwapOperation { offer_asset_info: native("usei"), ask_asset_info: native("ukusd") },
+    ]);
+
+    // disabled pairs are not walked
+    update_pair_status(deps.as_mut(), info.clone(), [native("usei"), native("ukusd")], true).unwrap();
+    let res = query_best_route(deps.as_ref(), offer_asset.clone(), native("ukusd")).unwrap();
+    assert_eq!(res.return_amount, Uint128::from(300u128));
+    assert_eq!(res.operations.len(), 1);
+    update_pair_status(deps.as_mut(), info.clone(), [native("usei"), native("ukusd")], false).unwrap();
+
+    // routes are limited to the max hops
+    let res = update_max_hops(deps.as_mut(), mock_info("other", &[]), 1);
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Admin });
+    update_max_hops(deps.as_mut(), info.clone(), 1).unwrap();
+    assert_eq!(query_config(deps.as_ref()).unwrap().max_hops, 1);
+    let res = query_best_route(deps.as_ref(), offer_asset.clone(), native("ukusd")).unwrap();
+    assert_eq!(res.return_amount, Uint128::from(300u128));
+    update_max_hops(deps.as_mut(), info.clone(), 3).unwrap();
+    let res = update_max_hops(deps.as_mut(), info.clone(), MAX_HOPS + 1);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter);
+
+    // no route at all
+    let res = query_best_route(deps.as_ref(), offer_asset.clone(), native("uatom"));
+    assert!(res.is_err());
+
+    // swap denom follows the discovered route
+    let res = swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd")]),
+                         Coin::new(100, "uusd"), "ukusd".to_string(), None, Some(true), None, None, None, None).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert_eq!(res.messages[0].id, SWAP_ROUTE_REPLY_ID);
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, funds, .. }) => {
+            assert_eq!(contract_addr, "pair_usd_sei");
+            assert_eq!(funds, &vec![Coin::new(100, "uusd")]);
+        }
+        _ => panic!("unexpected message"),
+    }
+}
+
+#[test]
+fn test_query_pairs_by_asset() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, _, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("usei"), native("ukusd")],
+                       Addr::unchecked("pair_sei_kusd"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("ukusd")],
+                       Addr::unchecked("pair_usd_kusd"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("ukusd"), native("uatom")],
+                       Addr::unchecked("pair_kusd_atom"), None, None).unwrap();
+
+    let res = query_pairs_by_asset(deps.as_ref(), native("usei"), None, None).unwrap();
+    let pair_addresses: Vec<Addr> = res.pair_configs.iter().map(|p| p.pair_address.clone()).collect();
+    assert_eq!(pair_addresses, vec![Addr::unchecked("pair_sei_kusd"), Addr::unchecked("pair_usd_sei")]);
+    assert_eq!(res.pair_configs[1].asset_infos, [native("uusd"), native("usei")]);
+
+    // paginate over the pairs of the asset, whichever side it was registered on
+    let res = query_pairs_by_asset(deps.as_ref(), native("ukusd"), None, Some(2)).unwrap();
+    let pair_addresses: Vec<Addr> = res.pair_configs.iter().map(|p| p.pair_address.clone()).collect();
+    assert_eq!(pair_addresses, vec![Addr::unchecked("pair_kusd_atom"), Addr::unchecked("pair_sei_kusd")]);
+    let res = query_pairs_by_asset(deps.as_ref(), native("ukusd"),
+                                   Some(res.pair_configs[1].asset_infos.clone()), Some(2)).unwrap();
+    let pair_addresses: Vec<Addr> = res.pair_configs.iter().map(|p| p.pair_address.clone()).collect();
+    assert_eq!(pair_addresses, vec![Addr::unchecked("pair_usd_kusd")]);
+
+    // re-registering a pair keeps a single index entry per asset
+    register_pair(&mut deps, info.clone(), [native("usei"), native("uusd")],
+                       Addr::unchecked("pair_usd_sei_v2"), None, None).unwrap();
+    let res = query_pairs_by_asset(deps.as_ref(), native("uusd"), None, None).unwrap();
+    let pair_addresses: Vec<Addr> = res.pair_configs.iter().map(|p| p.pair_address.clone()).collect();
+    assert_eq!(pair_addresses, vec![Addr::unchecked("pair_usd_kusd"), Addr::unchecked("pair_usd_sei_v2")]);
+
+    let res = query_pairs_by_asset(deps.as_ref(), native("uluna"), None, None).unwrap();
+    assert!(res.pair_configs.is_empty());
+}
+
+#[test]
+fn test_query_all_pair_configs() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), Some(Decimal::percent(1)), Some(Addr::unchecked("treasury"))).unwrap();
+    register_pair(&mut deps, info.clone(), [native("usei"), native("ukusd")],
+                       Addr::unchecked("pair_sei_kusd"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("ukusd")],
+                       Addr::unchecked("pair_usd_kusd"), None, None).unwrap();
+    update_pair_status(deps.as_mut(), info.clone(), [native("uusd"), native("ukusd")], true).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+
+    // accumulate some swap info on the first pair
+    swap_route(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd")]),
+               vec![SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") }], None, None).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(200, "usei")]);
+    reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+
+    let res = query_all_pair_configs(deps.as_ref(), None, Some(2)).unwrap();
+    assert_eq!(res.pair_configs.len(), 2);
+    assert_eq!(res.pair_configs[0].pair_config.pair_address, Addr::unchecked("pair_sei_kusd"));
+    assert_eq!(res.pair_configs[0].swap_info.total_amount_in, Uint128::zero());
+    assert_eq!(res.pair_configs[1].pair_config.pair_address, Addr::unchecked("pair_usd_kusd"));
+    assert!(res.pair_configs[1].pair_config.is_disabled);
+
+    let start_after = res.pair_configs[1].pair_config.asset_infos.clone();
+    let res = query_all_pair_configs(deps.as_ref(), Some(start_after), Some(2)).unwrap();
+    assert_eq!(res.pair_configs.len(), 1);
+    assert_eq!(res.pair_configs[0].pair_config.asset_infos, [native("uusd"), native("usei")]);
+    assert_eq!(res.pair_configs[0].pair_config.max_spread, Some(Decimal::percent(1)));
+    assert_eq!(res.pair_configs[0].pair_config.to, Some(Addr::unchecked("treasury")));
+    assert_eq!(res.pair_configs[0].swap_info.total_amount_in, Uint128::from(100u128));
+    assert_eq!(res.pair_configs[0].swap_info.total_amount_out, Uint128::from(200u128));
+}
+
+#[test]
+fn test_whitelist_batch() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, _, info, _) = mock_instantiate(msg);
+    let entries = vec![
+        (Addr::unchecked("caller_b"), true),
+        (Addr::unchecked("caller_a"), true),
+        (Addr::unchecked("caller_c"), false),
+    ];
+
+    let res = set_whitelist_batch(deps.as_mut(), mock_info("other", &[]), entries.clone());
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::WhitelistManager });
+    let res = set_whitelist_batch(deps.as_mut(), info.clone(), vec![]);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter);
+
+    let res = set_whitelist_batch(deps.as_mut(), info.clone(), entries).unwrap();
+    assert_eq!(res.events.len(), 3);
+    assert_eq!(res.events[0].ty, "swap_whitelist");
+    assert_eq!(res.events[0].attributes[0].value, "caller_b");
+    assert_eq!(res.events[2].attributes[1].value, "false");
+    assert!(query_is_swap_whitelist(deps.as_ref(), Addr::unchecked("caller_a")).unwrap());
+    assert!(!query_is_swap_whitelist(deps.as_ref(), Addr::unchecked("caller_c")).unwrap());
+
+    let res = set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller_d"), true).unwrap();
+    assert_eq!(res.events.len(), 1);
+
+    let res = query_whitelist(deps.as_ref(), None, Some(2)).unwrap();
+    let callers: Vec<Addr> = res.entries.iter().map(|e| e.caller.clone()).collect();
+    assert_eq!(callers, vec![Addr::unchecked("caller_a"), Addr::unchecked("caller_b")]);
+    let res = query_whitelist(deps.as_ref(), Some(Addr::unchecked("caller_b")), None).unwrap();
+    assert_eq!(res.entries.len(), 2);
+    assert_eq!(res.entries[0].caller, Addr::unchecked("caller_c"));
+    assert!(!res.entries[0].is_whitelist);
+    assert_eq!(res.entries[1].caller, Addr::unchecked("caller_d"));
+}
+
+#[test]
+fn test_ownership_proposal() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    let new_info = mock_info("new_owner", &[]);
+
+    let res = propose_new_owner(deps.as_mut(), env.clone(), new_info.clone(), "new_owner".to_string(), 100);
+    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+    let res = propose_new_owner(deps.as_mut(), env.clone(), info.clone(), CREATOR.to_string(), 100);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidOwner);
+    let res = propose_new_owner(deps.as_mut(), env.clone(), info.clone(), "new_owner".to_string(), MAX_PROPOSAL_TTL + 1);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter);
+    let res = claim_ownership(deps.as_mut(), env.clone(), new_info.clone());
+    assert_eq!(res.err().unwrap(), ContractError::OwnershipProposalNotFound {});
+
+    propose_new_owner(deps.as_mut(), env.clone(), info.clone(), "new_owner".to_string(), 100).unwrap();
+    let query_res = query_config(deps.as_ref()).unwrap();
+    assert_eq!(query_res.owner, Addr::unchecked(CREATOR));
+    assert_eq!(query_res.ownership_proposal, Some(OwnershipProposal {
+        owner: Addr::unchecked("new_owner"),
+        ttl: env.block.time.seconds() + 100,
+    }));
+
+    // only the proposed owner can claim
+    let res = claim_ownership(deps.as_mut(), env.clone(), mock_info("other", &[]));
+    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+
+    // the proposal expires
+    let mut expired_env = env.clone();
+    expired_env.block.time = env.block.time.plus_seconds(101);
+    let res = claim_ownership(deps.as_mut(), expired_env, new_info.clone());
+    assert_eq!(res.err().unwrap(), ContractError::OwnershipProposalExpired {});
+
+    // dropped proposals cannot be claimed
+    let res = drop_ownership_proposal(deps.as_mut(), new_info.clone());
+    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+    drop_ownership_proposal(deps.as_mut(), info.clone()).unwrap();
+    assert_eq!(query_config(deps.as_ref()).unwrap().ownership_proposal, None);
+    let res = claim_ownership(deps.as_mut(), env.clone(), new_info.clone());
+    assert_eq!(res.err().unwrap(), ContractError::OwnershipProposalNotFound {});
+
+    propose_new_owner(deps.as_mut(), env.clone(), info.clone(), "new_owner".to_string(), 100).unwrap();
+    claim_ownership(deps.as_mut(), env.clone(), new_info).unwrap();
+    let query_res = query_config(deps.as_ref()).unwrap();
+    assert_eq!(query_res.owner, Addr::unchecked("new_owner"));
+    assert_eq!(query_res.ownership_proposal, None);
+}
+
+#[test]
+fn test_roles() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, _, info, _) = mock_instantiate(msg);
+    let asset_infos = [native("uusd"), native("usei")];
+    let pair_manager = mock_info("pair_manager", &[]);
+    let guardian = mock_info("guardian", &[]);
+    let whitelist_manager = mock_info("whitelist_manager", &[]);
+    let admin = mock_info("admin", &[]);
+
+    // only the owner manages roles
+    let res = grant_role(deps.as_mut(), pair_manager.clone(), Role::PairManager, "pair_manager".to_string());
+    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+    grant_role(deps.as_mut(), info.clone(), Role::PairManager, "pair_manager".to_string()).unwrap();
+    grant_role(deps.as_mut(), info.clone(), Role::Guardian, "guardian".to_string()).unwrap();
+    grant_role(deps.as_mut(), info.clone(), Role::WhitelistManager, "whitelist_manager".to_string()).unwrap();
+    grant_role(deps.as_mut(), info.clone(), Role::Admin, "admin".to_string()).unwrap();
+
+    // pair manager
+    let res = register_pair(&mut deps, guardian.clone(), asset_infos.clone(), Addr::unchecked("pair"), None, None);
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::PairManager });
+    register_pair(&mut deps, pair_manager.clone(), asset_infos.clone(), Addr::unchecked("pair"), None, None).unwrap();
+    update_pair_max_spread(deps.as_mut(), pair_manager.clone(), asset_infos.clone(), Decimal::percent(1)).unwrap();
+    let res = set_whitelist(deps.as_mut(), pair_manager.clone(), Addr::unchecked("caller"), true);
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::WhitelistManager });
+
+    // guardian can disable but not enable a pair
+    let res = update_pair_status(deps.as_mut(), whitelist_manager.clone(), asset_infos.clone(), true);
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Guardian });
+    update_pair_status(deps.as_mut(), guardian.clone(), asset_infos.clone(), true).unwrap();
+    let res = update_pair_status(deps.as_mut(), guardian.clone(), asset_infos.clone(), false);
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::PairManager });
+    let res = update_pair_max_spread(deps.as_mut(), guardian.clone(), asset_infos.clone(), Decimal::percent(2));
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::PairManager });
+    update_pair_status(deps.as_mut(), pair_manager.clone(), asset_infos.clone(), false).unwrap();
+
+    // whitelist manager
+    set_whitelist(deps.as_mut(), whitelist_manager.clone(), Addr::unchecked("caller"), true).unwrap();
+    set_whitelist_batch(deps.as_mut(), whitelist_manager.clone(), vec![(Addr::unchecked("caller_2"), true)]).unwrap();
+
+    // admin holds every role but cannot manage roles
+    update_pair_status(deps.as_mut(), admin.clone(), asset_infos.clone(), true).unwrap();
+    set_whitelist(deps.as_mut(), admin.clone(), Addr::unchecked("caller_3"), true).unwrap();
+    update_max_hops(deps.as_mut(), admin.clone(), 2).unwrap();
+    let res = update_max_hops(deps.as_mut(), pair_manager.clone(), 2);
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Admin });
+    let res = grant_role(deps.as_mut(), admin.clone(), Role::Guardian, "other".to_string());
+    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+
+    let res = query_role_holders(deps.as_ref(), Role::Guardian, None, None).unwrap();
+    assert_eq!(res.holders, vec![Addr::unchecked("guardian")]);
+
+    // revoked roles are gone
+    let res = revoke_role(deps.as_mut(), admin.clone(), Role::Guardian, "guardian".to_string());
+    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+    revoke_role(deps.as_mut(), info.clone(), Role::Guardian, "guardian".to_string()).unwrap();
+    let res = update_pair_status(deps.as_mut(), guardian.clone(), asset_infos.clone(), true);
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Guardian });
+    let res = query_role_holders(deps.as_ref(), Role::Guardian, None, None).unwrap();
+    assert!(res.holders.is_empty());
+    let res = query_role_holders(deps.as_ref(), Role::Admin, None, None).unwrap();
+    assert_eq!(res.holders, vec![Addr::unchecked("admin")]);
+}
+
+#[test]
+fn test_pause() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    grant_role(deps.as_mut(), info.clone(), Role::Guardian, "guardian".to_string()).unwrap();
+    let caller = mock_info("caller", &[Coin::new(100, "uusd")]);
+    let operations = vec![SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") }];
+
+    let res = pause(deps.as_mut(), mock_info("other", &[]));
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Guardian });
+    pause(deps.as_mut(), mock_info("guardian", &[])).unwrap();
+    let query_res = query_config(deps.as_ref()).unwrap();
+    assert!(query_res.paused);
+    assert_eq!(query_res.paused_by, Some(Addr::unchecked("guardian")));
+
+    // swaps fail, admin messages keep working
+    let res = swap_route(deps.as_mut(), env.clone(), caller.clone(), operations.clone(), None, None);
+    assert_eq!(res.err().unwrap(), ContractError::Paused {});
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(), None, None, None, None, None, None);
+    assert_eq!(res.err().unwrap(), ContractError::Paused {});
+    update_pair_max_spread(deps.as_mut(), info.clone(), [native("uusd"), native("usei")], Decimal::percent(1)).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller_2"), true).unwrap();
+
+    // guardians cannot unpause
+    let res = unpause(deps.as_mut(), mock_info("guardian", &[]));
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Admin });
+    unpause(deps.as_mut(), info.clone()).unwrap();
+    let query_res = query_config(deps.as_ref()).unwrap();
+    assert!(!query_res.paused);
+    assert_eq!(query_res.paused_by, None);
+    swap_route(deps.as_mut(), env.clone(), caller, operations, None, None).unwrap();
+}
+
+#[test]
+fn test_swap_cw20() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    let token = AssetInfo::Token { contract_addr: Addr::unchecked("token_kusd") };
+    register_pair(&mut deps, info.clone(), [token.clone(), native("usei")],
+                       Addr::unchecked("pair_kusd_sei"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    deps.querier.with_token_balance("token_kusd", MOCK_CONTRACT_ADDR, Uint128::zero());
+    let token_info = mock_info("token_kusd", &[]);
+    let hook = |sender: &str, minimum_receive: Option<Uint128>| Cw20ReceiveMsg {
+        sender: sender.to_string(),
+        amount: Uint128::from(100u128),
+        msg: to_binary(&Cw20HookMsg::Swap {
+            target_asset: native("usei"),
+            to: None,
+            minimum_receive,
+        }).unwrap(),
+    };
+
+    let res = receive_cw20(deps.as_mut(), env.clone(), token_info.clone(), hook("other", None));
+    assert_eq!(res.err().unwrap(), ContractError::Unauthorized {});
+
+    // cw20 to native: the token is sent to the pair with its swap hook
+    let res = receive_cw20(deps.as_mut(), env.clone(), token_info.clone(), hook("caller", Some(Uint128::from(150u128)))).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::reply_on_success(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: "token_kusd".to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Send {
+            contract: "pair_kusd_sei".to_string(),
+            amount: Uint128::from(100u128),
+            msg: to_binary(&PairCw20HookMsg::Swap {
+                belief_price: None,
+                max_spread: None,
+                to: Some(MOCK_CONTRACT_ADDR.to_string()),
+            }).unwrap(),
+        }).unwrap(),
+        funds: vec![],
+    }), SWAP_ROUTE_REPLY_ID)]);
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(200, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "caller".to_string(),
+        amount: vec![Coin::new(200, "usei")],
+    }))]);
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+
+    // native to cw20: the output token is transferred to the recipient
+    let res = swap_route(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "usei")]),
+                         vec![SwapOperation { offer_asset_info: native("usei"), ask_asset_info: token.clone() }],
+                         None, Some("receiver".to_string())).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    deps.querier.with_token_balance("token_kusd", MOCK_CONTRACT_ADDR, Uint128::from(50u128));
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: "token_kusd".to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: "receiver".to_string(),
+            amount: Uint128::from(50u128),
+        }).unwrap(),
+        funds: vec![],
+    }))]);
+
+    let query_res = query_swap_info(deps.as_ref(), [token, native("usei")]).unwrap();
+    assert_eq!(query_res.total_amount_in, Uint128::from(200u128));
+    assert_eq!(query_res.total_amount_out, Uint128::from(250u128));
+}
+
+#[test]
+fn test_swap_denom_slippage() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), Some(Decimal::percent(5)), None).unwrap();
+    deps.querier.with_pair_rate("pair_usd_sei", "uusd", Decimal::percent(200));
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    let caller = mock_info("caller", &[Coin::new(100, "uusd")]);
+    let swap_msg = |belief_price: Option<Decimal>, max_spread: Option<Decimal>, to: Option<String>| {
+        CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "pair_usd_sei".to_string(),
+            msg: to_binary(&SwapMsg::Swap {
+                offer_asset: Asset { info: native("uusd"), amount: Uint128::from(100u128) },
+                belief_price,
+                max_spread,
+                to,
+            }).unwrap(),
+            funds: vec![Coin::new(100, "uusd")],
+        })
+    };
+
+    // the requested max spread is clamped by the max spread of the pair
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(), None,
+                         None, Some(Decimal::percent(2)), Some(Decimal::percent(10)), None, None).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::reply_on_success(
+        swap_msg(Some(Decimal::percent(2)), Some(Decimal::percent(5)), Some(MOCK_CONTRACT_ADDR.to_string())),
+        SWAP_ROUTE_REPLY_ID)]);
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(), None,
+                         None, None, Some(Decimal::percent(1)), None, None).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::reply_on_success(
+        swap_msg(None, Some(Decimal::percent(1)), Some(MOCK_CONTRACT_ADDR.to_string())),
+        SWAP_ROUTE_REPLY_ID)]);
+
+    // the minimum receive is checked against the output received by the router
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(), None,
+                         None, Some(Decimal::percent(50)), None, Some(Uint128::from(190u128)), None).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::reply_on_success(
+        swap_msg(Some(Decimal::percent(50)), Some(Decimal::percent(5)), Some(MOCK_CONTRACT_ADDR.to_string())),
+        SWAP_ROUTE_REPLY_ID)]);
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(180, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok());
+    assert_eq!(res.err().unwrap(), ContractError::AssertionMinimumReceive {
+        receive: Uint128::from(180u128),
+        minimum_receive: Uint128::from(190u128),
+    });
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(195, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "caller".to_string(),
+        amount: vec![Coin::new(195, "usei")],
+    }))]);
+
+    // the belief price only applies to the direct pair
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(), None,
+                         Some(true), Some(Decimal::percent(50)), None, None, None);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter {});
+}
+
+#[test]
+fn test_swap_denom_records_pair_events() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, Some(Addr::unchecked("receiver"))).unwrap();
+    deps.querier.with_pair_rate("pair_usd_sei", "uusd", Decimal::percent(200));
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+
+    // nothing is recorded before the pair swap completes
+    swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd")]), Coin::new(100, "uusd"),
+               "usei".to_string(), None, None, None, None, None, None).unwrap();
+    let query_res = query_swap_info(deps.as_ref(), [native("uusd"), native("usei")]).unwrap();
+    assert_eq!(query_res.total_amount_out, Uint128::zero());
+
+    // only the events of the pair are accounted
+    let pair_event = |contract: &str| {
+        // the chain prefixes the attributes with the reserved contract address
+        let mut event = Event::new("wasm");
+        event.attributes.push(Attribute { key: "_contract_address".to_string(), value: contract.to_string() });
+        event.add_attributes(vec![
+            ("action", "swap"),
+            ("return_amount", "190"),
+            ("spread_amount", "7"),
+            ("commission_amount", "3"),
+        ])
+    };
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(190, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), Reply {
+        id: SWAP_ROUTE_REPLY_ID,
+        result: SubMsgResult::Ok(SubMsgResponse {
+            events: vec![pair_event("pair_usd_sei"), pair_event("other_contract")],
+            data: None,
+        }),
+    }).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "receiver".to_string(),
+        amount: vec![Coin::new(190, "usei")],
+    }))]);
+    assert!(res.attributes.contains(&attr("spread_amount", "7")));
+    assert!(res.attributes.contains(&attr("commission_amount", "3")));
+
+    let query_res = query_swap_info(deps.as_ref(), [native("uusd"), native("usei")]).unwrap();
+    assert_eq!(query_res.total_amount_in, Uint128::from(100u128));
+    assert_eq!(query_res.total_amount_out, Uint128::from(190u128));
+    assert_eq!(query_res.total_spread_amount, Uint128::from(7u128));
+    assert_eq!(query_res.total_commission_amount, Uint128::from(3u128));
+}
+
+#[test]
+fn test_router_fee() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    deps.querier.with_pair_rate("pair_usd_sei", "uusd", Decimal::percent(200));
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+
+    // a fee needs a collector and is capped
+    let res = update_fee_config(deps.as_mut(), mock_info("other", &[]), 30, Some("collector".to_string()));
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Admin });
+    let res = update_fee_config(deps.as_mut(), info.clone(), 30, None);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter {});
+    let res = update_fee_config(deps.as_mut(), info.clone(), 1_001, Some("collector".to_string()));
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter {});
+    update_fee_config(deps.as_mut(), info.clone(), 30, Some("collector".to_string())).unwrap();
+    let query_res = query_config(deps.as_ref()).unwrap();
+    assert_eq!(query_res.fee_bps, 30);
+    assert_eq!(query_res.fee_collector, Some(Addr::unchecked("collector")));
+
+    // the fee is deducted from the offer amount before the pair swap
+    let caller = mock_info("caller", &[Coin::new(10_000, "uusd")]);
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(10_000, "uusd"), "usei".to_string(),
+                         None, None, None, None, None, None).unwrap();
+    assert_eq!(res.messages[0], SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "collector".to_string(),
+        amount: vec![Coin::new(30, "uusd")],
+    })));
+    match &res.messages[1].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => assert_eq!(funds, &vec![Coin::new(9_970, "uusd")]),
+        _ => panic!("unexpected message"),
+    }
+    assert!(res.attributes.contains(&attr("fee_amount", "30")));
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(19_940, "usei")]);
+    reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+    let query_res = query_swap_info(deps.as_ref(), [native("uusd"), native("usei")]).unwrap();
+    assert_eq!(query_res.total_amount_in, Uint128::from(9_970u128));
+    assert_eq!(query_res.total_fee_amount, Uint128::from(30u128));
+
+    // the simulation is net of the router fee
+    let simulation = |deps: &OwnedDeps<_, _, _>| -> SimulationResponse {
+        from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::QuerySimulation {
+            asset_infos: [native("uusd"), native("usei")],
+            offer_asset: Asset { info: native("uusd"), amount: Uint128::from(10_000u128) },
+        }).unwrap()).unwrap()
+    };
+    assert_eq!(simulation(&deps).return_amount, Uint128::from(19_940u128));
+
+    // the fee of the pair overrides the fee of the config
+    let res = update_pair_fee(deps.as_mut(), info.clone(), [native("uusd"), native("usei")], Some(1_001));
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter {});
+    update_pair_fee(deps.as_mut(), info.clone(), [native("uusd"), native("usei")], Some(0)).unwrap();
+    assert_eq!(query_pair_config(deps.as_ref(), [native("uusd"), native("usei")]).unwrap().fee_bps, Some(0));
+    assert_eq!(simulation(&deps).return_amount, Uint128::from(20_000u128));
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(10_000, "uusd"), "usei".to_string(),
+                         None, None, None, None, None, None).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    assert!(res.attributes.contains(&attr("fee_amount", "0")));
+}
+
+#[test]
+fn test_router_fee_swap_route_cw20() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    let token = AssetInfo::Token { contract_addr: Addr::unchecked("token_kusd") };
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                  Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [token.clone(), native("usei")],
+                  Addr::unchecked("pair_kusd_sei"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    update_fee_config(deps.as_mut(), info.clone(), 30, Some("collector".to_string())).unwrap();
+    update_pair_fee(deps.as_mut(), info.clone(), [token.clone(), native("usei")], Some(100)).unwrap();
+
+    // the route is charged the fee of its first pair
+    let res = swap_route(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(10_000, "uusd")]),
+                         vec![SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") }],
+                         None, None).unwrap();
+    assert_eq!(res.messages[0], SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "collector".to_string(),
+        amount: vec![Coin::new(30, "uusd")],
+    })));
+    match &res.messages[1].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => assert_eq!(funds, &vec![Coin::new(9_970, "uusd")]),
+        _ => panic!("unexpected message"),
+    }
+    assert!(res.attributes.contains(&attr("fee_amount", "30")));
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(9_970, "usei")]);
+    reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+    let query_res = query_swap_info(deps.as_ref(), [native("uusd"), native("usei")]).unwrap();
+    assert_eq!(query_res.total_amount_in, Uint128::from(9_970u128));
+    assert_eq!(query_res.total_fee_amount, Uint128::from(30u128));
+
+    // the fee of a cw20 swap is transferred in the token
+    deps.querier.with_token_balance("token_kusd", MOCK_CONTRACT_ADDR, Uint128::zero());
+    let res = receive_cw20(deps.as_mut(), env.clone(), mock_info("token_kusd", &[]), Cw20ReceiveMsg {
+        sender: "caller".to_string(),
+        amount: Uint128::from(1_000u128),
+        msg: to_binary(&Cw20HookMsg::Swap {
+            target_asset: native("usei"),
+            to: None,
+            minimum_receive: None,
+        }).unwrap(),
+    }).unwrap();
+    assert_eq!(res.messages[0], SubMsg::new(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: "token_kusd".to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Transfer {
+            recipient: "collector".to_string(),
+            amount: Uint128::from(10u128),
+        }).unwrap(),
+        funds: vec![],
+    })));
+    match &res.messages[1].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { msg, .. }) => match from_binary(msg).unwrap() {
+            Cw20ExecuteMsg::Send { amount, .. } => assert_eq!(amount, Uint128::from(990u128)),
+            _ => panic!("unexpected message"),
+        },
+        _ => panic!("unexpected message"),
+    }
+    assert!(res.attributes.contains(&attr("fee_amount", "10")));
+    let query_res = query_swap_info(deps.as_ref(), [token, native("usei")]).unwrap();
+    assert_eq!(query_res.total_fee_amount, Uint128::from(10u128));
+}
+
+#[test]
+fn test_caller_stats() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, mut env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("usei"), native("ukusd")],
+                       Addr::unchecked("pair_sei_kusd"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    let operations = vec![
+        SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") },
+        SwapOperation { offer_asset_info: native("usei"), ask_asset_info: native("ukusd") },
+    ];
+
+    let query_res = query_caller_stats(deps.as_ref(), Addr::unchecked("caller"), [native("uusd"), native("usei")]).unwrap();
+    assert_eq!(query_res.swap_count, 0);
+    assert_eq!(query_res.asset_infos, [native("uusd"), native("usei")]);
+
+    // every hop is accounted to the caller on its pair
+    swap_route(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd")]),
+               operations.clone(), None, None).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(200, "usei")]);
+    reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(300, "ukusd")]);
+    reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+
+    env.block.height += 10;
+    env.block.time = env.block.time.plus_seconds(60);
+    swap_route(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(50, "uusd")]),
+               vec![operations[0].clone()], None, None).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(100, "usei")]);
+    reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+
+    // the query accepts the assets in any order
+    let query_res = query_caller_stats(deps.as_ref(), Addr::unchecked("caller"), [native("usei"), native("uusd")]).unwrap();
+    assert_eq!(query_res.swap_count, 2);
+    assert_eq!(query_res.total_amount_in, Uint128::from(150u128));
+    assert_eq!(query_res.total_amount_out, Uint128::from(300u128));
+    assert_eq!(query_res.last_swap_height, env.block.height);
+    assert_eq!(query_res.last_swap_time, env.block.time.seconds());
+
+    let query_res = query_caller_stats_list(deps.as_ref(), Addr::unchecked("caller"), None, None).unwrap();
+    let pairs: Vec<[AssetInfo; 2]> = query_res.stats.iter().map(|stats| stats.asset_infos.clone()).collect();
+    assert_eq!(pairs, vec![[native("usei"), native("ukusd")], [native("uusd"), native("usei")]]);
+    assert_eq!(query_res.stats[0].total_amount_in, Uint128::from(200u128));
+    assert_eq!(query_res.stats[0].total_amount_out, Uint128::from(300u128));
+    let query_res = query_caller_stats_list(deps.as_ref(), Addr::unchecked("caller"),
+                                            Some([native("usei"), native("ukusd")]), Some(1)).unwrap();
+    assert_eq!(query_res.stats.len(), 1);
+    assert_eq!(query_res.stats[0].swap_count, 2);
+    let query_res = query_caller_stats_list(deps.as_ref(), Addr::unchecked("other"), None, None).unwrap();
+    assert!(query_res.stats.is_empty());
+}
+
+#[test]
+fn test_volume_history() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, mut env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    deps.querier.with_pair_rate("pair_usd_sei", "uusd", Decimal::percent(200));
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    let day_start = 1_690_156_800u64;
+
+    // two swaps in the first hour, one in the third hour of the same day
+    for (time, amount) in [(day_start + 60, 100u128), (day_start + 3_000, 50), (day_start + 7_500, 10)] {
+        env.block.time = Timestamp::from_seconds(time);
+        swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(amount, "uusd")]),
+                   Coin::new(amount, "uusd"), "usei".to_string(), None, None, None, None, None, None).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(amount * 2, "usei")]);
+        reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+    }
+
+    let res = query_volume_history(deps.as_ref(), [native("usei"), native("uusd")],
+                                   day_start, day_start + 86_400, Granularity::Hourly).unwrap();
+    let buckets: Vec<(u64, u64, Uint128, Uint128)> = res.buckets.iter()
+        .map(|b| (b.bucket_start, b.swap_count, b.amount_in, b.amount_out))
+        .collect();
+    assert_eq!(buckets, vec![
+        (day_start, 2, Uint128::from(150u128), Uint128::from(300u128)),
+        (day_start + 7_200, 1, Uint128::from(10u128), Uint128::from(20u128)),
+    ]);
+
+    let res = query_volume_history(deps.as_ref(), [native("uusd"), native("usei")],
+                                   day_start, day_start + 86_400, Granularity::Daily).unwrap();
+    assert_eq!(res.granularity, Granularity::Daily);
+    assert_eq!(res.buckets.len(), 1);
+    assert_eq!(res.buckets[0].swap_count, 3);
+    assert_eq!(res.buckets[0].amount_in, Uint128::from(160u128));
+
+    // the end of the range is exclusive
+    let res = query_volume_history(deps.as_ref(), [native("uusd"), native("usei")],
+                                   day_start + 1, day_start + 7_200, Granularity::Hourly).unwrap();
+    assert!(res.buckets.is_empty());
+}
+
+#[test]
+fn test_rate_limit() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, mut env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("ukusd")],
+                       Addr::unchecked("pair_usd_kusd"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller_2"), true).unwrap();
+    let rate_limit = RateLimit {
+        max_swap_amount: Some(Uint128::from(500u128)),
+        max_caller_volume: Some(Uint128::from(1_000u128)),
+        max_pair_volume: Some(Uint128::from(1_200u128)),
+        window_seconds: 3_600,
+    };
+    let swap = |deps: &mut OwnedDeps<_, _, _>, env: &Env, caller: &str, amount: u128, target: &str| {
+        swap_denom(deps.as_mut(), env.clone(), mock_info(caller, &[Coin::new(amount, "uusd")]),
+                   Coin::new(amount, "uusd"), target.to_string(), None, None, None, None, None, None)
+    };
+
+    let res = set_rate_limit(deps.as_mut(), mock_info("caller", &[]), native("uusd"), Some(rate_limit.clone()));
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Admin });
+    let res = set_rate_limit(deps.as_mut(), info.clone(), native("uusd"),
+                             Some(RateLimit { window_seconds: 0, ..rate_limit.clone() }));
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter {});
+    set_rate_limit(deps.as_mut(), info.clone(), native("uusd"), Some(rate_limit.clone())).unwrap();
+    assert_eq!(query_rate_limit(deps.as_ref(), native("uusd")).unwrap().rate_limit, Some(rate_limit));
+
+    env.block.time = Timestamp::from_seconds(36_000);
+    let res = swap(&mut deps, &env, "caller", 501, "usei");
+    assert_eq!(res.err().unwrap(), ContractError::SwapAmountLimitExceeded {
+        amount: Uint128::from(501u128),
+        max: Uint128::from(500u128),
+    });
+
+    // the volume of the caller is counted over every pair
+    swap(&mut deps, &env, "caller", 500, "usei").unwrap();
+    swap(&mut deps, &env, "caller", 500, "ukusd").unwrap();
+    let res = swap(&mut deps, &env, "caller", 1, "usei");
+    assert_eq!(res.err().unwrap(), ContractError::CallerVolumeLimitExceeded {
+        volume: Uint128::from(1_001u128),
+        max: Uint128::from(1_000u128),
+    });
+
+    // the volume of the pair is counted over every caller
+    swap(&mut deps, &env, "caller_2", 500, "usei").unwrap();
+    let res = swap(&mut deps, &env, "caller_2", 300, "usei");
+    assert_eq!(res.err().unwrap(), ContractError::PairVolumeLimitExceeded {
+        volume: Uint128::from(1_300u128),
+        max: Uint128::from(1_200u128),
+    });
+
+    // half way through the next window, half of the previous window still counts
+    env.block.time = Timestamp::from_seconds(36_000 + 3_600 + 1_800);
+    swap(&mut deps, &env, "caller", 500, "usei").unwrap();
+    let res = swap(&mut deps, &env, "caller", 1, "ukusd");
+    assert_eq!(res.err().unwrap(), ContractError::CallerVolumeLimitExceeded {
+        volume: Uint128::from(1_001u128),
+        max: Uint128::from(1_000u128),
+    });
+
+    // the volumes are forgotten once the sliding window moved past them
+    env.block.time = Timestamp::from_seconds(36_000 + 3 * 3_600);
+    swap(&mut deps, &env, "caller", 500, "usei").unwrap();
+    swap(&mut deps, &env, "caller", 500, "ukusd").unwrap();
+
+    // without limits the swaps are not tracked
+    set_rate_limit(deps.as_mut(), info.clone(), native("uusd"), None).unwrap();
+    assert_eq!(query_rate_limit(deps.as_ref(), native("uusd")).unwrap().rate_limit, None);
+    swap(&mut deps, &env, "caller", 5_000, "usei").unwrap();
+}
+
+#[test]
+fn test_rate_limit_route() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                  Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("usei"), native("ukusd")],
+                  Addr::unchecked("pair_sei_kusd"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    set_rate_limit(deps.as_mut(), info.clone(), native("usei"), Some(RateLimit {
+        max_swap_amount: None,
+        max_caller_volume: None,
+        max_pair_volume: Some(Uint128::from(150u128)),
+        window_seconds: 3_600,
+    })).unwrap();
+    let route = |deps: &mut OwnedDeps<MockStorage, MockApi, WasmMockQuerier>| {
+        swap_route(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd")]), vec![
+            SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") },
+            SwapOperation { offer_asset_info: native("usei"), ask_asset_info: native("ukusd") },
+        ], None, None).unwrap();
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(100, "usei")]);
+        let res = reply(deps.as_mut(), env.clone(), swap_reply_ok());
+        deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+        res
+    };
+
+    // the second hop is limited on the output of the first one
+    let res = route(&mut deps).unwrap();
+    assert_eq!(res.messages.len(), 1);
+    let res = route(&mut deps);
+    assert_eq!(res.err().unwrap(), ContractError::PairVolumeLimitExceeded {
+        volume: Uint128::from(200u128),
+        max: Uint128::from(150u128),
+    });
+}
+
+#[test]
+fn test_swap_denom_refund() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+
+    let res = swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(50, "uusd"), Coin::new(100, "uatom")]),
+                         Coin::new(100, "uusd"), "usei".to_string(), None, None, None, None, None, None);
+    assert_eq!(res.err().unwrap(), ContractError::InsufficientFunds {
+        denom: "uusd".to_string(),
+        required: Uint128::from(100u128),
+        provided: Uint128::from(50u128),
+    });
+
+    // only from_coin is offered to the pair, the rest goes back to the sender
+    let res = swap_denom(deps.as_mut(), env.clone(),
+                         mock_info("caller", &[Coin::new(150, "uusd"), Coin::new(10, "uatom")]),
+                         Coin::new(100, "uusd"), "usei".to_string(), None, None, None, None, None, None).unwrap();
+    assert_eq!(res.messages.len(), 2);
+    assert_eq!(res.messages[0], SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "caller".to_string(),
+        amount: vec![Coin::new(10, "uatom"), Coin::new(50, "uusd")],
+    })));
+    match &res.messages[1].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { funds, .. }) => assert_eq!(funds, &vec![Coin::new(100, "uusd")]),
+        _ => panic!("unexpected message"),
+    }
+    assert!(res.attributes.contains(&attr("refund", "10uatom,50uusd")));
+
+    // an exact payment sends nothing back
+    let res = swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd")]),
+                         Coin::new(100, "uusd"), "usei".to_string(), None, None, None, None, None, None).unwrap();
+    assert_eq!(res.messages.len(), 1);
+}
+
+#[test]
+fn test_pair_validation() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, _, info, _) = mock_instantiate(msg);
+    let asset_infos = [native("uusd"), native("usei")];
+    let invalid_pair = |pair: &str, reason: &str| ContractError::InvalidPair {
+        pair: pair.to_string(),
+        reason: reason.to_string(),
+    };
+
+    // the address must be a pair contract
+    let res = update_pair_config(deps.as_mut(), info.clone(), asset_infos.clone(),
+                                 Addr::unchecked("no_pair"), None, None, PairType::default());
+    match res.err().unwrap() {
+        ContractError::InvalidPair { pair, reason } => {
+            assert_eq!(pair, "no_pair");
+            assert!(reason.starts_with("pair info query failed"));
+        }
+        e => panic!("unexpected error: {:?}", e),
+    }
+    let res = update_pair_config(deps.as_mut(), info.clone(), asset_infos.clone(),
+                                 Addr::unchecked("Pair_Usd_Sei"), None, None, PairType::default());
+    assert!(matches!(res.err().unwrap(), ContractError::Std(_)));
+
+    // the pair must trade both assets
+    deps.querier.with_pair_info("pair_usd_kusd", &[native("uusd"), native("ukusd")]);
+    let res = update_pair_config(deps.as_mut(), info.clone(), asset_infos.clone(),
+                                 Addr::unchecked("pair_usd_kusd"), None, None, PairType::default());
+    assert_eq!(res.err().unwrap(), invalid_pair("pair_usd_kusd", "expected assets uusd, usei but the pair trades uusd, ukusd"));
+    let res = update_pair_config(deps.as_mut(), info.clone(), [native("uusd"), native("uusd")],
+                                 Addr::unchecked("pair_usd_kusd"), None, None, PairType::default());
+    assert_eq!(res.err().unwrap(), invalid_pair("pair_usd_kusd", "both assets are uusd"));
+
+    // the order of the assets does not matter
+    deps.querier.with_pair_info("pair_usd_sei", &[native("usei"), native("uusd")]);
+    update_pair_config(deps.as_mut(), info.clone(), asset_infos.clone(),
+                       Addr::unchecked("pair_usd_sei"), None, None, PairType::default()).unwrap();
+    assert_eq!(query_pair_config(deps.as_ref(), asset_infos).unwrap().pair_address, Addr::unchecked("pair_usd_sei"));
+}
+
+#[test]
+fn test_pair_types() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    let token = AssetInfo::Token { contract_addr: Addr::unchecked("token_kusd") };
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    deps.querier.with_pair_info("pair_stable", &[native("uusd"), native("usei"), token.clone()]);
+    deps.querier.with_pair_info("pair_terraswap", &[native("uusd"), native("ukusd")]);
+
+    // the native dex module is not supported yet
+    let res = update_pair_config(deps.as_mut(), info.clone(), [native("uusd"), native("usei")],
+                                 Addr::unchecked("pair_stable"), None, None, PairType::NativeDex);
+    assert_eq!(res.err().unwrap(), ContractError::UnsupportedPairType { pair_type: PairType::NativeDex });
+
+    // only the stable pools may trade more than the two assets
+    let res = update_pair_config(deps.as_mut(), info.clone(), [native("uusd"), native("usei")],
+                                 Addr::unchecked("pair_stable"), None, None, PairType::AstroportXyk);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidPair {
+        pair: "pair_stable".to_string(),
+        reason: "expected assets uusd, usei but the pair trades uusd, usei, token_kusd".to_string(),
+    });
+    let res = update_pair_config(deps.as_mut(), info.clone(), [native("uusd"), native("ukusd")],
+                                 Addr::unchecked("pair_stable"), None, None, PairType::AstroportStable);
+    assert!(matches!(res.err().unwrap(), ContractError::InvalidPair { .. }));
+
+    // the stable pairs are told the ask asset of their pool
+    update_pair_config(deps.as_mut(), info.clone(), [native("uusd"), native("usei")],
+                       Addr::unchecked("pair_stable"), None, None, PairType::AstroportStable).unwrap();
+    assert_eq!(query_pair_config(deps.as_ref(), [native("uusd"), native("usei")]).unwrap().pair_type,
+               PairType::AstroportStable);
+    let res = swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd")]),
+                         Coin::new(100, "uusd"), "usei".to_string(), None, None, None, None, None, None).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::reply_on_success(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: "pair_stable".to_string(),
+        msg: to_binary(&StableSwapMsg::Swap {
+            offer_asset: Asset { info: native("uusd"), amount: Uint128::from(100u128) },
+            ask_asset_info: Some(native("usei")),
+            belief_price: None,
+            max_spread: None,
+            to: Some(MOCK_CONTRACT_ADDR.to_string()),
+        }).unwrap(),
+        funds: vec![Coin::new(100, "uusd")],
+    }), SWAP_ROUTE_REPLY_ID)]);
+    deps.querier.with_pair_rate("pair_stable", "uusd", Decimal::percent(99));
+    let res: SimulationResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::QuerySimulation {
+        asset_infos: [native("uusd"), native("usei")],
+        offer_asset: Asset { info: native("uusd"), amount: Uint128::from(100u128) },
+    }).unwrap()).unwrap();
+    assert_eq!(res.return_amount, Uint128::from(99u128));
+
+    // and so are the stable pairs receiving a cw20 token
+    deps.querier.with_pair_info("pair_stable_kusd", &[token.clone(), native("usei")]);
+    update_pair_config(deps.as_mut(), info.clone(), [token.clone(), native("usei")],
+                       Addr::unchecked("pair_stable_kusd"), None, None, PairType::AstroportStable).unwrap();
+    deps.querier.with_token_balance("token_kusd", MOCK_CONTRACT_ADDR, Uint128::zero());
+    let res = receive_cw20(deps.as_mut(), env.clone(), mock_info("token_kusd", &[]), Cw20ReceiveMsg {
+        sender: "caller".to_string(),
+        amount: Uint128::from(100u128),
+        msg: to_binary(&Cw20HookMsg::Swap { target_asset: native("usei"), to: None, minimum_receive: None }).unwrap(),
+    }).unwrap();
+    assert_eq!(res.messages[0].msg, CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: "token_kusd".to_string(),
+        msg: to_binary(&Cw20ExecuteMsg::Send {
+            contract: "pair_stable_kusd".to_string(),
+            amount: Uint128::from(100u128),
+            msg: to_binary(&StableCw20HookMsg::Swap {
+                ask_asset_info: Some(native("usei")),
+                belief_price: None,
+                max_spread: None,
+                to: Some(MOCK_CONTRACT_ADDR.to_string()),
+            }).unwrap(),
+        }).unwrap(),
+        funds: vec![],
+    }));
+
+    // terraswap pairs take the classic swap message
+    update_pair_config(deps.as_mut(), info.clone(), [native("uusd"), native("ukusd")],
+                       Addr::unchecked("pair_terraswap"), None, None, PairType::Terraswap).unwrap();
+    let res = swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd")]),
+                         Coin::new(100, "uusd"), "ukusd".to_string(), None, None, None, None, None, None).unwrap();
+    match &res.messages[0].msg {
+        CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, msg, .. }) => {
+            assert_eq!(contract_addr, "pair_terraswap");
+            assert_eq!(msg, &to_binary(&SwapMsg::Swap {
+                offer_asset: Asset { info: native("uusd"), amount: Uint128::from(100u128) },
+                belief_price: None,
+                max_spread: None,
+                to: Some(MOCK_CONTRACT_ADDR.to_string()),
+            }).unwrap());
+        }
+        _ => panic!("unexpected message"),
+    }
+}
+
+#[test]
+fn test_split_swap() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    let asset_infos = [native("uusd"), native("usei")];
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    register_pair(&mut deps, info.clone(), asset_infos.clone(), Addr::unchecked("pair_usd_sei"), None,
+                  Some(Addr::unchecked("receiver"))).unwrap();
+
+    // only the pair managers register pools trading the assets of the pair
+    deps.querier.with_pair_info("pool_usd_sei", &asset_infos);
+    deps.querier.with_pair_info("pool_usd_kusd", &[native("uusd"), native("ukusd")]);
+    let res = add_pair_pool(deps.as_mut(), mock_info("caller", &[]), asset_infos.clone(), "pool_usd_sei".to_string());
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::PairManager });
+    let res = add_pair_pool(deps.as_mut(), info.clone(), asset_infos.clone(), "pool_usd_kusd".to_string());
+    assert!(matches!(res.err().unwrap(), ContractError::InvalidPair { .. }));
+    let res = add_pair_pool(deps.as_mut(), info.clone(), asset_infos.clone(), "pair_usd_sei".to_string());
+    assert_eq!(res.err().unwrap(), ContractError::InvalidPair {
+        pair: "pair_usd_sei".to_string(),
+        reason: "pool already registered".to_string(),
+    });
+    add_pair_pool(deps.as_mut(), info.clone(), asset_infos.clone(), "pool_usd_sei".to_string()).unwrap();
+    assert_eq!(query_pair_config(deps.as_ref(), asset_infos.clone()).unwrap().pools, vec![Addr::unchecked("pool_usd_sei")]);
+
+    // the deeper pool takes the larger share of the order
+    deps.querier.with_pair_reserves("pair_usd_sei", "uusd", Uint128::from(1_000_000u128), Uint128::from(1_000_000u128));
+    deps.querier.with_pair_reserves("pool_usd_sei", "uusd", Uint128::from(3_000_000u128), Uint128::from(3_000_000u128));
+    let caller = mock_info("caller", &[Coin::new(100_000, "uusd")]);
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100_000, "uusd"), "usei".to_string(),
+                         None, None, None, None, None, Some(true)).unwrap();
+    assert!(res.attributes.contains(&attr("split_offers", "pair_usd_sei:30000,pool_usd_sei:70000")));
+    let pool_swap = |pool: &str, amount: u128| SubMsg::reply_on_success(CosmosMsg::Wasm(WasmMsg::Execute {
+        contract_addr: pool.to_string(),
+        msg: to_binary(&SwapMsg::Swap {
+            offer_asset: Asset { info: native("uusd"), amount: Uint128::from(amount) },
+            belief_price: None,
+            max_spread: None,
+            to: Some(MOCK_CONTRACT_ADDR.to_string()),
+        }).unwrap(),
+        funds: vec![Coin::new(amount, "uusd")],
+    }), SWAP_ROUTE_REPLY_ID);
+    assert_eq!(res.messages, vec![pool_swap("pair_usd_sei", 30_000), pool_swap("pool_usd_sei", 70_000)]);
+
+    // the outputs of the pools are aggregated once the last pool replied
+    let pool_reply = |pool: &str, spread: &str| {
+        let mut event = Event::new("wasm");
+        event.attributes.push(Attribute { key: "_contract_address".to_string(), value: pool.to_string() });
+        Reply {
+            id: SWAP_ROUTE_REPLY_ID,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![event.add_attributes(vec![("spread_amount", spread), ("commission_amount", "1")])],
+                data: None,
+            }),
+        }
+    };
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(29_000, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), pool_reply("pair_usd_sei", "800")).unwrap();
+    assert!(res.messages.is_empty());
+    assert!(res.attributes.contains(&attr("action", "swap_route_pool")));
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(97_000, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), pool_reply("pool_usd_sei", "1500")).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "receiver".to_string(),
+        amount: vec![Coin::new(97_000, "usei")],
+    }))]);
+    let swap_info = query_swap_info(deps.as_ref(), asset_infos.clone()).unwrap();
+    assert_eq!(swap_info.total_amount_in, Uint128::from(100_000u128));
+    assert_eq!(swap_info.total_amount_out, Uint128::from(97_000u128));
+    assert_eq!(swap_info.total_spread_amount, Uint128::from(2_300u128));
+    assert_eq!(swap_info.total_commission_amount, Uint128::from(2u128));
+    let caller_stats = query_caller_stats(deps.as_ref(), Addr::unchecked("caller"), asset_infos.clone()).unwrap();
+    assert_eq!(caller_stats.swap_count, 1);
+
+    // the min chunk and the max pools bound the split
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+    let res = update_split_config(deps.as_mut(), info.clone(), 0, Uint128::zero());
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter {});
+    update_split_config(deps.as_mut(), info.clone(), 2, Uint128::from(60_000u128)).unwrap();
+    assert_eq!(query_config(deps.as_ref()).unwrap().min_split_chunk, Uint128::from(60_000u128));
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100_000, "uusd"), "usei".to_string(),
+                         None, None, None, None, None, Some(true)).unwrap();
+    assert_eq!(res.messages, vec![pool_swap("pair_usd_sei", 40_000), pool_swap("pool_usd_sei", 60_000)]);
+    update_split_config(deps.as_mut(), info.clone(), 1, Uint128::zero()).unwrap();
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100_000, "uusd"), "usei".to_string(),
+                         None, None, None, None, None, Some(true)).unwrap();
+    assert_eq!(res.messages, vec![pool_swap("pair_usd_sei", 100_000)]);
+
+    // the split needs the direct pair
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100_000, "uusd"), "usei".to_string(),
+                         None, Some(true), None, None, None, Some(true));
+    assert_eq!(res.err().unwrap(), ContractError::InvalidParameter {});
+
+    let res = remove_pair_pool(deps.as_mut(), info.clone(), asset_infos.clone(), "pool_usd_kusd".to_string());
+    assert_eq!(res.err().unwrap(), ContractError::InvalidPair {
+        pair: "pool_usd_kusd".to_string(),
+        reason: "pool not registered".to_string(),
+    });
+    remove_pair_pool(deps.as_mut(), info.clone(), asset_infos.clone(), "pool_usd_sei".to_string()).unwrap();
+    assert!(query_pair_config(deps.as_ref(), asset_infos).unwrap().pools.is_empty());
+}
+
+#[test]
+fn test_swap_denom_exact_out() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")], Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    update_fee_config(deps.as_mut(), info.clone(), 100, Some("collector".to_string())).unwrap();
+    deps.querier.with_pair_reverse_rate("pair_usd_sei", "usei", Decimal::percent(200));
+
+    // the pair needs 200 uusd for 100 usei, 202 once the 1% router fee is added
+    let res = swap_denom_exact_out(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(300, "uusd")]),
+                                   Coin::new(201, "uusd"), Coin::new(100, "usei"), None);
+    assert_eq!(res.err().unwrap(), ContractError::MaxInputExceeded {
+        required: Uint128::from(202u128),
+        max: Uint128::from(201u128),
+    });
+    let res = swap_denom_exact_out(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(150, "uusd")]),
+                                   Coin::new(300, "uusd"), Coin::new(100, "usei"), None);
+    assert_eq!(res.err().unwrap(), ContractError::InsufficientFunds {
+        denom: "uusd".to_string(),
+        required: Uint128::from(300u128),
+        provided: Uint128::from(150u128),
+    });
+    let res = swap_denom_exact_out(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(300, "uusd")]),
+                                   Coin::new(300, "uusd"), Coin::new(100, "uusd"), None);
+    assert_eq!(res.err().unwrap(), ContractError::InvalidDenom {});
+
+    // only the required input is swapped, the rest of the funds is refunded
+    let res = swap_denom_exact_out(deps.as_mut(), env.clone(),
+                                   mock_info("caller", &[Coin::new(300, "uusd"), Coin::new(5, "ukusd")]),
+                                   Coin::new(300, "uusd"), Coin::new(100, "usei"), None).unwrap();
+    assert_eq!(res.messages, vec![
+        SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "caller".to_string(),
+            amount: vec![Coin::new(5, "ukusd"), Coin::new(98, "uusd")],
+        })),
+        SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+            to_address: "collector".to_string(),
+            amount: vec![Coin::new(2, "uusd")],
+        })),
+        SubMsg::reply_on_success(CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: "pair_usd_sei".to_string(),
+            msg: to_binary(&SwapMsg::Swap {
+                offer_asset: Asset { info: native("uusd"), amount: Uint128::from(200u128) },
+                belief_price: None,
+                max_spread: None,
+                to: Some(MOCK_CONTRACT_ADDR.to_string()),
+            }).unwrap(),
+            funds: vec![Coin::new(200, "uusd")],
+        }), SWAP_ROUTE_REPLY_ID),
+    ]);
+    assert!(res.attributes.contains(&attr("from_coin", "202uusd")));
+
+    // the swap reverts unless the target amount is delivered
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(99, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok());
+    assert_eq!(res.err().unwrap(), ContractError::AssertionMinimumReceive {
+        receive: Uint128::from(99u128),
+        minimum_receive: Uint128::from(100u128),
+    });
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(100, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    assert_eq!(res.messages, vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "caller".to_string(),
+        amount: vec![Coin::new(100, "usei")],
+    }))]);
+    let swap_info = query_swap_info(deps.as_ref(), [native("uusd"), native("usei")]).unwrap();
+    assert_eq!(swap_info.total_amount_in, Uint128::from(200u128));
+    assert_eq!(swap_info.total_fee_amount, Uint128::from(2u128));
+}
+
+#[test]
+fn test_oracle_price_deviation() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    let asset_infos = [native("uusd"), native("usei")];
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    register_pair(&mut deps, info.clone(), asset_infos.clone(), Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    deps.querier.with_oracle_price("oracle", "uusd", Decimal256::one());
+    deps.querier.with_oracle_price("oracle", "usei", Decimal256::percent(50));
+    deps.querier.with_pair_rate("pair_usd_sei", "uusd", Decimal::percent(180));
+
+    // only the admins set the oracle, only the pair managers set the max deviation
+    let res = update_oracle(deps.as_mut(), mock_info("caller", &[]), Some("oracle".to_string()));
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::Admin });
+    let res = update_pair_max_oracle_deviation(deps.as_mut(), mock_info("caller", &[]), asset_infos.clone(), None);
+    assert_eq!(res.err().unwrap(), ContractError::MissingRole { role: Role::PairManager });
+
+    // nothing is checked until both the oracle and the max deviation of the pair are set
+    let caller = mock_info("caller", &[Coin::new(100, "uusd")]);
+    update_oracle(deps.as_mut(), info.clone(), Some("oracle".to_string())).unwrap();
+    assert_eq!(query_config(deps.as_ref()).unwrap().oracle, Some(Addr::unchecked("oracle")));
+    swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(),
+               None, None, None, None, None, None).unwrap();
+
+    // the pair returns 1.8 usei per uusd while the oracle quotes 2
+    update_pair_max_oracle_deviation(deps.as_mut(), info.clone(), asset_infos.clone(), Some(Decimal::percent(5))).unwrap();
+    assert_eq!(query_pair_config(deps.as_ref(), asset_infos.clone()).unwrap().max_oracle_deviation, Some(Decimal::percent(5)));
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(),
+                         None, None, None, None, None, None);
+    assert_eq!(res.err().unwrap(), ContractError::OraclePriceDeviation {
+        pair: "pair_usd_sei".to_string(),
+        deviation: Decimal256::percent(10),
+        max: Decimal::percent(5),
+    });
+    let res = swap_route(deps.as_mut(), env.clone(), caller.clone(), vec![SwapOperation {
+        offer_asset_info: native("uusd"),
+        ask_asset_info: native("usei"),
+    }], None, None);
+    assert!(matches!(res.err().unwrap(), ContractError::OraclePriceDeviation { .. }));
+
+    // within the max deviation
+    deps.querier.with_pair_rate("pair_usd_sei", "uusd", Decimal::percent(196));
+    swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(),
+               None, None, None, None, None, None).unwrap();
+
+    // the swap fails without an oracle price
+    deps.querier.with_pair_rate("pair_usd_kusd", "uusd", Decimal::one());
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("ukusd")], Addr::unchecked("pair_usd_kusd"), None, None).unwrap();
+    update_pair_max_oracle_deviation(deps.as_mut(), info.clone(), [native("uusd"), native("ukusd")],
+                                     Some(Decimal::percent(5))).unwrap();
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "ukusd".to_string(),
+                         None, None, None, None, None, None);
+    assert!(matches!(res.err().unwrap(), ContractError::Std(_)));
+
+    // removing the oracle disables the check
+    update_oracle(deps.as_mut(), info.clone(), None).unwrap();
+    swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "ukusd".to_string(),
+               None, None, None, None, None, None).unwrap();
+}
+
+#[test]
+fn test_twap() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    let asset_infos = [native("uusd"), native("usei")];
+    let pair_key = crate::helper::pair_key(&asset_infos);
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    register_pair(&mut deps, info.clone(), asset_infos.clone(), Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("ukusd")], Addr::unchecked("pair_usd_kusd"), None, None).unwrap();
+    let at = |seconds: u64| {
+        let mut env = env.clone();
+        env.block.time = env.block.time.plus_seconds(seconds);
+        env
+    };
+
+    // anyone snapshots the pairs reporting cumulative prices
+    deps.querier.with_cumulative_prices("pair_usd_sei", &asset_infos, Uint128::zero(), Uint128::zero());
+    let res = snapshot_prices(deps.as_mut(), at(0)).unwrap();
+    assert!(res.attributes.contains(&attr("snapshots", "1")));
+    let res = query_twap(deps.as_ref(), at(99), asset_infos.clone(), 100);
+    assert_eq!(res.err().unwrap(), StdError::generic_err("No price snapshot old enough for the window"));
+    let res = query_twap(deps.as_ref(), at(100), asset_infos.clone(), 0);
+    assert!(res.is_err());
+    let res = query_twap(deps.as_ref(), at(100), asset_infos.clone(), MAX_TWAP_WINDOW + 1);
+    assert!(res.is_err());
+
+    deps.querier.with_cumulative_prices("pair_usd_sei", &asset_infos, Uint128::from(200u128), Uint128::from(50u128));
+    let twap = query_twap(deps.as_ref(), at(100), asset_infos.clone(), 100).unwrap();
+    assert_eq!(twap.asset_infos, asset_infos);
+    assert_eq!(twap.price0_average, Decimal::percent(200));
+    assert_eq!(twap.price1_average, Decimal::percent(50));
+    assert_eq!((twap.start_time, twap.end_time), (env.block.time.seconds(), env.block.time.seconds() + 100));
+
+    // each swap snapshots its pair
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![]);
+    swap_denom(deps.as_mut(), at(100), mock_info("caller", &[Coin::new(100, "uusd")]), Coin::new(100, "uusd"),
+               "usei".to_string(), None, None, None, None, None, None).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(190, "usei")]);
+    reply(deps.as_mut(), at(100), swap_reply_ok()).unwrap();
+    deps.querier.with_cumulative_prices("pair_usd_sei", &asset_infos, Uint128::from(350u128), Uint128::from(75u128));
+    let twap = query_twap(deps.as_ref(), at(150), asset_infos.clone(), 50).unwrap();
+    assert_eq!(twap.price0_average, Decimal::percent(300));
+    assert_eq!(twap.start_time, env.block.time.seconds() + 100);
+    let twap = query_twap(deps.as_ref(), at(150), asset_infos.clone(), 100).unwrap();
+    assert_eq!(twap.price0_average, Decimal::from_ratio(350u128, 150u128));
+
+    // the cumulative prices wrap on overflow
+    deps.querier.with_cumulative_prices("pair_usd_sei", &asset_infos, Uint128::MAX - Uint128::from(99u128),
+                                        Uint128::from(75u128));
+    snapshot_prices(deps.as_mut(), at(200)).unwrap();
+    deps.querier.with_cumulative_prices("pair_usd_sei", &asset_infos, Uint128::from(100u128), Uint128::from(75u128));
+    let twap = query_twap(deps.as_ref(), at(300), asset_infos.clone(), 100).unwrap();
+    assert_eq!(twap.price0_average, Decimal::percent(200));
+    assert_eq!(twap.price1_average, Decimal::zero());
+
+    // the snapshots older than the max window are pruned, but the latest of them
+    deps.querier.with_cumulative_prices("pair_usd_sei", &asset_infos, Uint128::from(200 + 2 * (MAX_TWAP_WINDOW as u128 + 50)),
+                                        Uint128::from(75u128));
+    snapshot_prices(deps.as_mut(), at(MAX_TWAP_WINDOW + 150)).unwrap();
+    assert!(!PRICE_SNAPSHOTS.has(&deps.storage, (&pair_key, env.block.time.seconds())));
+    assert!(PRICE_SNAPSHOTS.has(&deps.storage, (&pair_key, env.block.time.seconds() + 100)));
+    let twap = query_twap(deps.as_ref(), at(MAX_TWAP_WINDOW + 150), asset_infos, MAX_TWAP_WINDOW).unwrap();
+    assert_eq!(twap.start_time, env.block.time.seconds() + 100);
+    assert_eq!(twap.price0_average, Decimal::percent(200));
+}
+
+#[test]
+fn test_simulation_batch() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")], Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("ukusd")], Addr::unchecked("pair_usd_kusd"), None, None).unwrap();
+    deps.querier.with_pair_rate("pair_usd_sei", "uusd", Decimal::percent(200));
+    deps.querier.with_pair_rate("pair_usd_sei", "usei", Decimal::percent(50));
+    update_fee_config(deps.as_mut(), info.clone(), 100, Some("collector".to_string())).unwrap();
+    let offer = |denom: &str, amount: u128| Asset { info: native(denom), amount: Uint128::from(amount) };
+
+    // the failing requests report their error, the others are simulated net of the router fee
+    let res: SimulationBatchResponse = from_binary(&query(deps.as_ref(), env.clone(), QueryMsg::QuerySimulationBatch {
+        requests: vec![
+            ([native("uusd"), native("usei")], offer("uusd", 1_000)),
+            ([native("usei"), native("uusd")], offer("usei", 1_000)),
+            ([native("uusd"), native("uatom")], offer("uusd", 1_000)),
+            ([native("uusd"), native("ukusd")], offer("uusd", 1_000)),
+        ],
+    }).unwrap()).unwrap();
+    let simulation = |return_amount: u128| SimulationBatchResult {
+        simulation: Some(SimulationResponse {
+            return_amount: Uint128::from(return_amount),
+            spread_amount: Uint128::zero(),
+            commission_amount: Uint128::zero(),
+        }),
+        error: None,
+    };
+    assert_eq!(res.results.len(), 4);
+    assert_eq!(res.results[0], simulation(1_980));
+    assert_eq!(res.results[1], simulation(495));
+    assert_eq!(res.results[2], SimulationBatchResult {
+        simulation: None,
+        error: Some("Generic error: Pair uusd-uatom not found".to_string()),
+    });
+    assert!(res.results[3].simulation.is_none());
+    assert!(res.results[3].error.as_ref().unwrap().contains("pair_usd_kusd"));
+
+    // the batch is bounded
+    let res = query(deps.as_ref(), env, QueryMsg::QuerySimulationBatch {
+        requests: vec![([native("uusd"), native("usei")], offer("uusd", 1_000)); 31],
+    });
+    assert!(res.is_err());
+}
+
+#[test]
+fn test_quote() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, _, info, _) = mock_instantiate(msg);
+    let asset_infos = [native("uusd"), native("usei")];
+    register_pair(&mut deps, info.clone(), asset_infos.clone(), Addr::unchecked("pair_usd_sei"), Some(Decimal::percent(2)), None).unwrap();
+    deps.querier.with_pair_reserves("pair_usd_sei", "uusd", Uint128::from(1_000_000u128), Uint128::from(2_000_000u128));
+    update_fee_config(deps.as_mut(), info.clone(), 100, Some("collector".to_string())).unwrap();
+    let offer = |amount: u128| Asset { info: native("uusd"), amount: Uint128::from(amount) };
+
+    // 99_000 uusd reach the pair once the router fee is deducted
+    let quote = query_quote(deps.as_ref(), offer(100_000), native("usei")).unwrap();
+    assert_eq!(quote.pair_address, Addr::unchecked("pair_usd_sei"));
+    assert_eq!(quote.operations, vec![SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") }]);
+    assert!(!quote.is_disabled);
+    assert_eq!(quote.max_spread, Some(Decimal::percent(2)));
+    assert_eq!(quote.fee_amount, Uint128::from(1_000u128));
+    assert_eq!(quote.return_amount, Uint128::from(180_163u128));
+    assert_eq!(quote.hops, vec![HopQuote {
+        ask_asset_info: native("usei"),
+        return_amount: Uint128::from(180_163u128),
+        spread_amount: Uint128::from(17_837u128),
+        commission_amount: Uint128::zero(),
+    }]);
+    assert_eq!(quote.effective_price, Decimal::from_ratio(180_163u128, 100_000u128));
+    // the reference offer of 99 uusd returns 197 usei
+    let pair_price = Decimal::from_ratio(180_163u128, 99_000u128);
+    let reference_price = Decimal::from_ratio(197u128, 99u128);
+    assert_eq!(quote.price_impact, (reference_price - pair_price) / reference_price);
+
+    // the impact is never negative, even when the rounding of the reference offer favors the offer
+    let quote = query_quote(deps.as_ref(), offer(100), native("usei")).unwrap();
+    assert_eq!(quote.price_impact, Decimal::zero());
+
+    // without a direct pair the best route is quoted, with the tightest max spread of its pairs
+    register_pair(&mut deps, info.clone(), [native("usei"), native("ukusd")], Addr::unchecked("pair_sei_kusd"),
+                  Some(Decimal::percent(1)), None).unwrap();
+    deps.querier.with_pair_rate("pair_sei_kusd", "usei", Decimal::percent(50));
+    let quote = query_quote(deps.as_ref(), offer(100_000), native("ukusd")).unwrap();
+    assert_eq!(quote.operations, vec![
+        SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") },
+        SwapOperation { offer_asset_info: native("usei"), ask_asset_info: native("ukusd") },
+    ]);
+    assert_eq!(quote.pair_address, Addr::unchecked("pair_usd_sei"));
+    assert_eq!(quote.max_spread, Some(Decimal::percent(1)));
+    assert_eq!(quote.fee_amount, Uint128::from(1_000u128));
+    assert_eq!(quote.return_amount, Uint128::from(90_081u128));
+    // the spread of each hop is reported in its own ask asset
+    assert_eq!(quote.hops, vec![
+        HopQuote {
+            ask_asset_info: native("usei"),
+            return_amount: Uint128::from(180_163u128),
+            spread_amount: Uint128::from(17_837u128),
+            commission_amount: Uint128::zero(),
+        },
+        HopQuote {
+            ask_asset_info: native("ukusd"),
+            return_amount: Uint128::from(90_081u128),
+            spread_amount: Uint128::zero(),
+            commission_amount: Uint128::zero(),
+        },
+    ]);
+    assert_eq!(quote.effective_price, Decimal::from_ratio(90_081u128, 100_000u128));
+    // the reference offer of 99 uusd returns 197 usei, then 98 ukusd
+    let route_price = Decimal::from_ratio(90_081u128, 99_000u128);
+    let reference_price = Decimal::from_ratio(98u128, 99u128);
+    assert_eq!(quote.price_impact, (reference_price - route_price) / reference_price);
+
+    // the disabled pairs are quoted as such
+    update_pair_status(deps.as_mut(), info.clone(), asset_infos, true).unwrap();
+    assert!(query_quote(deps.as_ref(), offer(100_000), native("usei")).unwrap().is_disabled);
+
+    assert!(query_quote(deps.as_ref(), offer(100_000), native("uatom")).is_err());
+    assert!(query_quote(deps.as_ref(), offer(0), native("usei")).is_err());
+}
+
+#[test]
+fn test_pair_lookup_errors() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                  Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    let missing = [native("uusd"), native("uatom")];
+    let not_found = || ContractError::PairNotFound { pair: "uusd-uatom".to_string() };
+    let caller = mock_info("caller", &[Coin::new(100, "uusd")]);
+
+    // state accessors
+    assert_eq!(read_pair_config(&deps.storage, &missing).unwrap_err(), not_found());
+    assert_eq!(read_swap_info(&deps.storage, &[native("uusd"), native("usei")]).unwrap_err(),
+               ContractError::SwapInfoNotFound { pair: "uusd-usei".to_string() });
+
+    // pair management
+    assert_eq!(update_pair_status(deps.as_mut(), info.clone(), missing.clone(), true).unwrap_err(), not_found());
+    assert_eq!(update_pair_max_spread(deps.as_mut(), info.clone(), missing.clone(), Decimal::percent(1)).unwrap_err(), not_found());
+    assert_eq!(update_pair_max_oracle_deviation(deps.as_mut(), info.clone(), missing.clone(), None).unwrap_err(), not_found());
+    assert_eq!(update_pair_fee(deps.as_mut(), info.clone(), missing.clone(), Some(10)).unwrap_err(), not_found());
+    assert_eq!(add_pair_pool(deps.as_mut(), info.clone(), missing.clone(), "pool".to_string()).unwrap_err(), not_found());
+    assert_eq!(remove_pair_pool(deps.as_mut(), info.clone(), missing.clone(), "pool".to_string()).unwrap_err(), not_found());
+
+    // swaps
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "uatom".to_string(),
+                         None, None, None, None, None, None);
+    assert_eq!(res.unwrap_err(), not_found());
+    let res = swap_denom_exact_out(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), Coin::new(50, "uatom"), None);
+    assert_eq!(res.unwrap_err(), not_found());
+    let res = swap_route(deps.as_mut(), env.clone(), caller.clone(),
+                         vec![SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("uatom") }], None, None);
+    assert_eq!(res.unwrap_err(), not_found());
+    let res = receive_cw20(deps.as_mut(), env.clone(), mock_info("token_kusd", &[]), Cw20ReceiveMsg {
+        sender: "caller".to_string(),
+        amount: Uint128::from(100u128),
+        msg: to_binary(&Cw20HookMsg::Swap { target_asset: native("uatom"), to: None, minimum_receive: None }).unwrap(),
+    });
+    assert_eq!(res.unwrap_err(), ContractError::PairNotFound { pair: "token_kusd-uatom".to_string() });
+
+    // queries
+    let expected = StdError::generic_err("Pair uusd-uatom not found");
+    assert_eq!(query_pair_config(deps.as_ref(), missing.clone()).unwrap_err(), expected);
+    assert_eq!(query_twap(deps.as_ref(), env.clone(), missing.clone(), 60).unwrap_err(), expected);
+    assert_eq!(query_quote(deps.as_ref(), Asset { info: native("uusd"), amount: Uint128::from(100u128) }, native("uatom")).unwrap_err(),
+               StdError::generic_err("No route found from uusd to uatom"));
+    for msg in [
+        QueryMsg::QuerySimulation { asset_infos: missing.clone(), offer_asset: Asset { info: native("uusd"), amount: Uint128::from(100u128) } },
+        QueryMsg::QueryReverseSimulation { asset_infos: missing.clone(), ask_asset: Asset { info: native("uatom"), amount: Uint128::from(100u128) } },
+        QueryMsg::QueryCumulativePrices { asset_infos: missing.clone() },
+    ] {
+        assert_eq!(query(deps.as_ref(), env.clone(), msg).unwrap_err(), expected);
+    }
+
+    // a disabled pair is reported as such
+    update_pair_status(deps.as_mut(), info, [native("uusd"), native("usei")], true).unwrap();
+    let disabled = || ContractError::PairConfigDisabled { pair: "uusd-usei".to_string() };
+    let res = swap_denom(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), "usei".to_string(),
+                         None, None, None, None, None, None);
+    assert_eq!(res.unwrap_err(), disabled());
+    let res = swap_denom_exact_out(deps.as_mut(), env.clone(), caller.clone(), Coin::new(100, "uusd"), Coin::new(50, "usei"), None);
+    assert_eq!(res.unwrap_err(), disabled());
+    let res = swap_route(deps.as_mut(), env, caller,
+                         vec![SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("usei") }], None, None);
+    assert_eq!(res.unwrap_err(), disabled());
+}
+
+#[test]
+fn test_legacy_config() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, _, _, _) = mock_instantiate(msg);
+    // the config stored by the contract before the router settings were added
+    deps.storage.set(b"config", br#"{"owner":"creator"}"#);
+
+    let config = query_config(deps.as_ref()).unwrap();
+    assert_eq!(config.owner, Addr::unchecked(CREATOR));
+    assert_eq!(config.max_hops, DEFAULT_MAX_HOPS);
+    assert!(!config.paused);
+    assert_eq!(config.fee_bps, 0);
+    assert_eq!(config.fee_collector, None);
+    assert_eq!(config.max_split_pools, DEFAULT_MAX_SPLIT_POOLS);
+    assert_eq!(config.min_split_chunk, Uint128::zero());
+    assert_eq!(config.oracle, None);
+}
+
+#[test]
+fn test_migrate_legacy_pair_configs() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, _, _) = mock_instantiate(msg);
+    let legacy = |pair_address: &str| LegacyPairConfig {
+        pair_address: Addr::unchecked(pair_address),
+        is_disabled: false,
+        max_spread: Some(Decimal::percent(1)),
+        to: None,
+    };
+    let usd_sei = [native("uusd"), native("usei")];
+    let sei_kusd = [native("usei"), native("ukusd")];
+    LEGACY_PAIR_CONFIGS.save(&mut deps.storage, &pair_key(&usd_sei), &legacy("pair_usd_sei")).unwrap();
+    LEGACY_PAIR_CONFIGS.save(&mut deps.storage, &pair_key(&sei_kusd), &legacy("pair_sei_kusd")).unwrap();
+    assert!(query_pair_config(deps.as_ref(), usd_sei.clone()).is_err());
+
+    // the assets must match a stored pair, nothing is migrated otherwise
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg {
+        pairs: vec![[native("uusd"), native("uatom")], usd_sei.clone()],
+    });
+    assert_eq!(res.unwrap_err(), ContractError::PairNotFound { pair: "uusd-uatom".to_string() });
+
+    let res = migrate(deps.as_mut(), env.clone(), MigrateMsg { pairs: vec![usd_sei.clone(), sei_kusd.clone()] }).unwrap();
+    assert_eq!(res.attributes, vec![attr("action", "migrate"), attr("migrated_pairs", "2")]);
+    let pair_config = query_pair_config(deps.as_ref(), usd_sei.clone()).unwrap();
+    assert_eq!(pair_config.pair_address, Addr::unchecked("pair_usd_sei"));
+    assert_eq!(pair_config.asset_infos, usd_sei);
+    assert_eq!(pair_config.max_spread, Some(Decimal::percent(1)));
+    assert_eq!(pair_config.pair_type, PairType::AstroportXyk);
+
+    // the migrated pairs are indexed by asset
+    let res = query_pairs_by_asset(deps.as_ref(), native("usei"), None, None).unwrap();
+    let pair_addresses: Vec<Addr> = res.pair_configs.iter().map(|p| p.pair_address.clone()).collect();
+    assert_eq!(pair_addresses, vec![Addr::unchecked("pair_sei_kusd"), Addr::unchecked("pair_usd_sei")]);
+
+    // migrating again leaves the pairs untouched
+    let res = migrate(deps.as_mut(), env, MigrateMsg { pairs: vec![usd_sei] }).unwrap();
+    assert_eq!(res.attributes, vec![attr("action", "migrate"), attr("migrated_pairs", "0")]);
+}
+
+#[test]
+fn test_swap_denom_refund_target_denom() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, env, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("usei")],
+                  Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    set_whitelist(deps.as_mut(), info.clone(), Addr::unchecked("caller"), true).unwrap();
+    deps.querier.with_pair_reverse_rate("pair_usd_sei", "usei", Decimal::percent(200));
+    let output = |amount: u128| vec![SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "caller".to_string(),
+        amount: vec![Coin::new(amount, "usei")],
+    }))];
+
+    // the router holds 1_000 usei, the 30 usei attached to the swap are refunded before the pair swap
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(1_030, "usei")]);
+    let res = swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd"), Coin::new(30, "usei")]),
+                         Coin::new(100, "uusd"), "usei".to_string(), None, None, None, None, None, None).unwrap();
+    assert_eq!(res.messages[0], SubMsg::new(CosmosMsg::Bank(BankMsg::Send {
+        to_address: "caller".to_string(),
+        amount: vec![Coin::new(30, "usei")],
+    })));
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(1_200, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    assert_eq!(res.messages, output(200));
+
+    // the refund may exceed the output of the pair
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(1_500, "usei")]);
+    swap_denom(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(100, "uusd"), Coin::new(500, "usei")]),
+               Coin::new(100, "uusd"), "usei".to_string(), None, None, None, None, None, None).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(1_200, "usei")]);
+    let res = reply(deps.as_mut(), env.clone(), swap_reply_ok()).unwrap();
+    assert_eq!(res.messages, output(200));
+
+    // the exact output swap refunds the target denom the same way
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(1_030, "usei")]);
+    swap_denom_exact_out(deps.as_mut(), env.clone(), mock_info("caller", &[Coin::new(300, "uusd"), Coin::new(30, "usei")]),
+                         Coin::new(300, "uusd"), Coin::new(100, "usei"), None).unwrap();
+    deps.querier.update_balance(MOCK_CONTRACT_ADDR, vec![Coin::new(1_100, "usei")]);
+    let res = reply(deps.as_mut(), env, swap_reply_ok()).unwrap();
+    assert_eq!(res.messages, output(100));
+}
+
+#[test]
+fn test_update_pair_config_keeps_settings() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, _, info, _) = mock_instantiate(msg);
+    let asset_infos = [native("uusd"), native("usei")];
+    register_pair(&mut deps, info.clone(), asset_infos.clone(), Addr::unchecked("pair_usd_sei"), None, None).unwrap();
+    deps.querier.with_pair_info("pool_usd_sei", &asset_infos);
+    add_pair_pool(deps.as_mut(), info.clone(), asset_infos.clone(), "pool_usd_sei".to_string()).unwrap();
+    update_pair_fee(deps.as_mut(), info.clone(), asset_infos.clone(), Some(10)).unwrap();
+    update_pair_max_oracle_deviation(deps.as_mut(), info.clone(), asset_infos.clone(), Some(Decimal::percent(5))).unwrap();
+
+    // registering the pair again overrides the settings that are set and keeps the pools, fee and oracle guard
+    register_pair(&mut deps, info.clone(), asset_infos.clone(), Addr::unchecked("pair_usd_sei"),
+                  Some(Decimal::percent(2)), Some(Addr::unchecked("treasury"))).unwrap();
+    let pair_config = query_pair_config(deps.as_ref(), asset_infos.clone()).unwrap();
+    assert_eq!(pair_config.max_spread, Some(Decimal::percent(2)));
+    assert_eq!(pair_config.to, Some(Addr::unchecked("treasury")));
+    assert_eq!(pair_config.pools, vec![Addr::unchecked("pool_usd_sei")]);
+    assert_eq!(pair_config.fee_bps, Some(10));
+    assert_eq!(pair_config.max_oracle_deviation, Some(Decimal::percent(5)));
+
+    // a disabled pair stays disabled, the settings not overridden are kept
+    update_pair_status(deps.as_mut(), info.clone(), asset_infos.clone(), true).unwrap();
+    register_pair(&mut deps, info.clone(), asset_infos.clone(), Addr::unchecked("pair_usd_sei"),
+                  Some(Decimal::percent(3)), None).unwrap();
+    let pair_config = query_pair_config(deps.as_ref(), asset_infos.clone()).unwrap();
+    assert!(pair_config.is_disabled);
+    assert_eq!(pair_config.max_spread, Some(Decimal::percent(3)));
+    assert_eq!(pair_config.to, Some(Addr::unchecked("treasury")));
+    assert_eq!(pair_config.pools, vec![Addr::unchecked("pool_usd_sei")]);
+
+    // the pools are dropped when the type of the pair changes
+    update_pair_config(deps.as_mut(), info.clone(), asset_infos.clone(), Addr::unchecked("pair_usd_sei"), None, None,
+                       PairType::Terraswap).unwrap();
+    let pair_config = query_pair_config(deps.as_ref(), asset_infos.clone()).unwrap();
+    assert_eq!(pair_config.pair_type, PairType::Terraswap);
+    assert!(pair_config.pools.is_empty());
+    assert!(pair_config.is_disabled);
+    assert_eq!(pair_config.fee_bps, Some(10));
+
+    // an additional pool registered as the pair address is not kept twice
+    add_pair_pool(deps.as_mut(), info.clone(), asset_infos.clone(), "pool_usd_sei".to_string()).unwrap();
+    update_pair_config(deps.as_mut(), info, asset_infos.clone(), Addr::unchecked("pool_usd_sei"), None, None,
+                       PairType::Terraswap).unwrap();
+    let pair_config = query_pair_config(deps.as_ref(), asset_infos).unwrap();
+    assert_eq!(pair_config.pair_address, Addr::unchecked("pool_usd_sei"));
+    assert!(pair_config.pools.is_empty());
+}
+
+#[test]
+fn test_best_route_candidates() {
+    let msg = mock_instantiate_msg();
+    let (mut deps, _, info, _) = mock_instantiate(msg);
+    register_pair(&mut deps, info.clone(), [native("uusd"), native("ukusd")],
+                  Addr::unchecked("pair_usd_kusd"), None, None).unwrap();
+    deps.querier.with_pair_rate("pair_usd_kusd", "uusd", Decimal::one());
+    // twelve two hop routes, the later ones return more
+    for i in 0..12u64 {
+        let denom = format!("u{:02}", i);
+        let first_pair = format!("pair_usd_{}", denom);
+        let second_pair = format!("pair_{}_kusd", denom);
+        register_pair(&mut deps, info.clone(), [native("uusd"), native(&denom)],
+                      Addr::unchecked(&first_pair), None, None).unwrap();
+        register_pair(&mut deps, info.clone(), [native(&denom), native("ukusd")],
+                      Addr::unchecked(&second_pair), None, None).unwrap();
+        deps.querier.with_pair_rate(&first_pair, "uusd", Decimal::one());
+        deps.querier.with_pair_rate(&second_pair, &denom, Decimal::percent(200 + i));
+    }
+    let offer_asset = Asset { info: native("uusd"), amount: Uint128::from(100u128) };
+
+    // the direct pair and the first nine two hop routes are the only candidates simulated
+    let res = query_best_route(deps.as_ref(), offer_asset, native("ukusd")).unwrap();
+    assert_eq!(res.operations, vec![
+        SwapOperation { offer_asset_info: native("uusd"), ask_asset_info: native("u08") },
+        SwapOperation { offer_asset_info: native("u08"), ask_asset_info: native("ukusd") },
+    ]);
+    assert_eq!(res.return_amount, Uint128::from(208u128));
+}